@@ -0,0 +1,55 @@
+use mapper::config::ConfigFormat;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// 読み込む設定ファイル
+#[derive(Debug, Clone)]
+pub enum ConfigFile {
+  // JSON、TOML、YAML
+  Data(PathBuf, ConfigFormat),
+  // xremap互換のRuby DSL
+  Xremap(PathBuf),
+  // Ruby DSLをrubyでJSONに変換してから読む。rumapのparserが対応していない書き方をするとき用
+  XremapRuby(PathBuf),
+}
+
+impl ConfigFile {
+  // formatを指定しなければ拡張子で決め、わからなければJSONとして読む
+  pub fn data(path: PathBuf, format: Option<ConfigFormat>) -> Self {
+    let format = format
+      .or_else(|| ConfigFormat::from_extension(&path))
+      .unwrap_or(ConfigFormat::Json);
+    ConfigFile::Data(path, format)
+  }
+
+  pub fn path(&self) -> &Path {
+    match self {
+      ConfigFile::Data(path, _) | ConfigFile::Xremap(path) | ConfigFile::XremapRuby(path) => path,
+    }
+  }
+
+  pub fn load(&self) -> Result<mapper::config::Config, Box<dyn std::error::Error>> {
+    match self {
+      ConfigFile::Data(path, format) => Ok(format.parse(&std::fs::read_to_string(path)?)?),
+      ConfigFile::Xremap(path) => Ok(mapper::config::dsl::parse_dsl(&std::fs::read_to_string(
+        path,
+      )?)?),
+      ConfigFile::XremapRuby(path) => {
+        let mut converter_rb = tempfile::NamedTempFile::new()?;
+        let converter_rb_source = include_str!("../convert.rb");
+        converter_rb.write_all(converter_rb_source.as_bytes())?;
+        let output = Command::new("ruby")
+          .arg(converter_rb.path())
+          .arg(path)
+          .output()?;
+        if !output.status.success() {
+          return Err(String::from_utf8_lossy(&output.stderr).into_owned().into());
+        }
+        Ok(serde_json::from_reader::<&[u8], mapper::config::Config>(
+          output.stdout.as_ref(),
+        )?)
+      }
+    }
+  }
+}
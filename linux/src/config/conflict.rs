@@ -0,0 +1,85 @@
+use super::*;
+use crate::*;
+use itertools::Itertools;
+use mapper::Conflict;
+
+pub type XConflict = mapper::Conflict<XAppIdentifier, XKeySymbol, XModifier>;
+pub type XTrigger = mapper::Trigger<XKeySymbol, XModifier>;
+
+// 使われないkeybindを、設定に書く名前で説明する
+pub fn conflict_warnings(keybind_for_focus: &XKeyBindForFocus) -> Vec<String> {
+  keybind_for_focus
+    .conflicts()
+    .iter()
+    .map(describe_conflict)
+    .collect()
+}
+
+pub fn describe_conflict(conflict: &XConflict) -> String {
+  match conflict {
+    Conflict::Duplicate { layer, trigger } => format!(
+      "{}: `{}` is bound more than once, only the first one is used",
+      describe_layer(layer),
+      describe_trigger(trigger)
+    ),
+    Conflict::Shadowed { layer, trigger, by } => format!(
+      "{}: `{}` never fires because `{}` also matches it",
+      describe_layer(layer),
+      describe_trigger(trigger),
+      describe_trigger(by)
+    ),
+    Conflict::ShadowsGlobal {
+      layer,
+      trigger,
+      global,
+    } => format!(
+      "{}: `{}` hides the global `{}`",
+      describe_layer(layer),
+      describe_trigger(trigger),
+      describe_trigger(global)
+    ),
+  }
+}
+
+fn describe_layer(layer: &mapper::Layer<XAppIdentifier>) -> String {
+  match layer {
+    mapper::Layer::Mode(mode) => format!("modes[{}]", mode.name()),
+    mapper::Layer::Application(application) => format!("in_app[{}]", application.identifier()),
+    mapper::Layer::Matcher(matcher) => format!("window {}", matcher),
+    mapper::Layer::Global => "remap".to_string(),
+  }
+}
+
+// "C-x C-f" や "j+k" のように設定と同じ形で書く
+pub fn describe_trigger(trigger: &XTrigger) -> String {
+  trigger
+    .prefix
+    .iter()
+    .map(describe_keyinput)
+    .chain(std::iter::once(
+      trigger.keys.iter().map(describe_keyinput).join("+"),
+    ))
+    .join(" ")
+}
+
+fn describe_keyinput(key_input: &KeyInput) -> String {
+  let key = key_input.key().raw_value();
+  key_input
+    .modifiers()
+    .to_vec()
+    .iter()
+    .map(|modifier| {
+      let mask = modifier.raw_value();
+      keysyms::MASK_TO_MODIFIERNAME
+        .get(&mask)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| mask.to_string())
+    })
+    .chain(std::iter::once(
+      keysyms::KEYSYM_TO_KEYNAME
+        .get(&key)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| key.to_string()),
+    ))
+    .join("-")
+}
@@ -1,6 +1,14 @@
+mod config_file;
+mod conflict;
 pub mod keysyms;
 mod parser;
+mod reloader;
+mod suggestion;
 mod x_into_domain;
 
+pub use config_file::*;
+pub use conflict::*;
 pub use parser::*;
+pub use reloader::*;
+pub use suggestion::*;
 pub use x_into_domain::*;
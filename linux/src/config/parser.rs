@@ -3,3 +3,6 @@ use crate::*;
 
 pub type XParser<'a> =
   mapper::config::Parser<'a, XAppIdentifier, XKeySymbol, XModifier, XExecution, XIntoDomain>;
+
+pub type XLayoutParser<'a> =
+  mapper::config::Parser<'a, XAppIdentifier, XKeySymbol, XModifier, XExecution, XLayoutIntoDomain>;
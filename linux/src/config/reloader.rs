@@ -0,0 +1,118 @@
+use super::*;
+use crate::*;
+use mapper::config::IsParser;
+
+pub type XKeyBindForFocus =
+  mapper::KeyBindForFocus<'static, XAppIdentifier, XKeySymbol, XModifier, XExecution>;
+
+// CapsLockとNumLockは設定しなくても無視する
+pub const DEFAULT_IGNORE_MODIFIERS: [XModifier; 2] = [x11::xlib::LockMask, x11::xlib::Mod2Mask];
+
+type BuiltConfig = (XKeyBindForFocus, PossibleKeyinputFinder);
+
+// 設定からkeybindとgrabするkeyinputを作る
+pub fn build_config(
+  config: mapper::config::Config,
+) -> Result<BuiltConfig, mapper::config::InvalidConfigError> {
+  let parser = XParser::build(&XIntoDomain);
+  let keybind_for_focus = parser.build_keybind_for_focus(config.clone())?;
+  let possible_keyinput_finder = parser.build_possible_keyinput_finder(config)?;
+  Ok(with_default_ignore_modifiers(
+    keybind_for_focus,
+    possible_keyinput_finder,
+  ))
+}
+
+// X serverの今のkeyboardの配列に合わせて、Shiftなどで出すkeysymを元のkeyとmodifierにする
+pub fn build_config_for_layout(
+  config: mapper::config::Config,
+  into_domain: &'static XLayoutIntoDomain,
+) -> Result<BuiltConfig, mapper::config::InvalidConfigError> {
+  let parser = XLayoutParser::build(into_domain);
+  let keybind_for_focus = parser.build_keybind_for_focus(config.clone())?;
+  let possible_keyinput_finder = parser.build_possible_keyinput_finder(config)?;
+  Ok(with_default_ignore_modifiers(
+    keybind_for_focus,
+    possible_keyinput_finder,
+  ))
+}
+
+fn with_default_ignore_modifiers(
+  keybind_for_focus: XKeyBindForFocus,
+  possible_keyinput_finder: PossibleKeyinputFinder,
+) -> BuiltConfig {
+  let ignored_modifiers = possible_keyinput_finder
+    .ignored_modifiers()
+    .merge(&Modifiers::new(
+      DEFAULT_IGNORE_MODIFIERS
+        .iter()
+        .cloned()
+        .map(Modifier::new)
+        .collect(),
+    ));
+  (
+    keybind_for_focus,
+    possible_keyinput_finder.with_ignored_modifiers(ignored_modifiers),
+  )
+}
+
+pub struct XReloader {
+  config_file: ConfigFile,
+  into_domain: Option<&'static XLayoutIntoDomain>,
+  // 最後に読み込めた設定。keyboardの配列が変わったときはこれから作り直す
+  config: std::cell::RefCell<Option<mapper::config::Config>>,
+}
+
+impl XReloader {
+  pub fn new(config_file: ConfigFile) -> Self {
+    Self {
+      config_file,
+      into_domain: None,
+      config: std::cell::RefCell::new(None),
+    }
+  }
+
+  // 起動時に読み込んだ設定
+  pub fn with_config(self, config: mapper::config::Config) -> Self {
+    self.config.replace(Some(config));
+    self
+  }
+
+  fn build(
+    &self,
+    config: mapper::config::Config,
+  ) -> Result<BuiltConfig, Box<dyn std::error::Error>> {
+    Ok(match self.into_domain {
+      Some(into_domain) => build_config_for_layout(config, into_domain)?,
+      None => build_config(config)?,
+    })
+  }
+
+  // 読み直すときも、起動時と同じようにkeyboardの配列に合わせる
+  pub fn with_layout(mut self, into_domain: &'static XLayoutIntoDomain) -> Self {
+    self.into_domain = Some(into_domain);
+    self
+  }
+}
+
+impl mapper::IsReloader<XAppIdentifier, XKeySymbol, XModifier, XKeyBindForFocus> for XReloader {
+  fn reload(
+    &self,
+  ) -> Result<(XKeyBindForFocus, PossibleKeyinputFinder), Box<dyn std::error::Error>> {
+    let config = self.config_file.load()?;
+    let (keybind_for_focus, possible_keyinput_finder) = self.build(config.clone())?;
+    for warning in conflict_warnings(&keybind_for_focus) {
+      log::warn!("{}: {}", self.config_file.path().display(), warning);
+    }
+    self.config.replace(Some(config));
+    Ok((keybind_for_focus, possible_keyinput_finder))
+  }
+
+  // 同じ設定なので、衝突の警告は読み込んだときに出したものだけにする
+  fn rebuild(
+    &self,
+  ) -> Result<(XKeyBindForFocus, PossibleKeyinputFinder), Box<dyn std::error::Error>> {
+    let config = self.config.borrow().clone().ok_or("no config loaded")?;
+    self.build(config)
+  }
+}
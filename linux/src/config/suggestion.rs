@@ -0,0 +1,73 @@
+use mapper::config::InvalidConfigError;
+
+// 提案する候補の最大数
+const MAX_SUGGESTIONS: usize = 3;
+
+// typoしたkey名やmodifier名に近い名前を、近いものから返す
+pub fn suggest_names(error: &InvalidConfigError) -> Vec<&'static str> {
+  match error.kind() {
+    InvalidConfigError::UnexpectedKey(name) => {
+      closest_names(name, super::keysyms::KEYNAME_TO_KEYSYM.keys().cloned())
+    }
+    InvalidConfigError::UnexpectedModifier(name) => {
+      closest_names(name, super::keysyms::MODIFIERNAME_TO_MASK.keys().cloned())
+    }
+    _ => vec![],
+  }
+}
+
+// 大文字小文字を無視した編集距離が、名前の長さの1/3以内のもの
+// 1文字の名前は大文字小文字の違いだけ、2文字の名前は1文字違いまでにする
+// 同じ距離なら、大文字の数が近いもの、文字を足すか消すだけで届くもの、大文字小文字まで合っているものの順にする
+pub fn closest_names(
+  name: &str,
+  candidates: impl Iterator<Item = &'static str>,
+) -> Vec<&'static str> {
+  let lowercase = name.to_lowercase();
+  let uppercase_count = |name: &str| name.chars().filter(|c| c.is_uppercase()).count();
+  let threshold = match name.chars().count() {
+    0 | 1 => 0,
+    length => std::cmp::max(1, length / 3),
+  };
+  let mut scored: Vec<(usize, usize, bool, usize, &'static str)> = candidates
+    .map(|candidate| {
+      let candidate_lowercase = candidate.to_lowercase();
+      (
+        edit_distance(&lowercase, &candidate_lowercase),
+        (uppercase_count(name) as isize - uppercase_count(candidate) as isize).unsigned_abs(),
+        !is_subsequence(&lowercase, &candidate_lowercase)
+          && !is_subsequence(&candidate_lowercase, &lowercase),
+        edit_distance(name, candidate),
+        candidate,
+      )
+    })
+    .filter(|(distance, ..)| *distance <= threshold)
+    .collect();
+  scored.sort();
+  scored
+    .into_iter()
+    .take(MAX_SUGGESTIONS)
+    .map(|(.., candidate)| candidate)
+    .collect()
+}
+
+// aの文字をすべて、順番どおりにbが含む
+fn is_subsequence(a: &str, b: &str) -> bool {
+  let mut b = b.chars();
+  a.chars().all(|a_char| b.any(|b_char| b_char == a_char))
+}
+
+// Levenshtein距離
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut previous: Vec<usize> = (0..=b.len()).collect();
+  for (i, a_char) in a.chars().enumerate() {
+    let mut current = vec![i + 1];
+    for (j, b_char) in b.iter().enumerate() {
+      let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+      current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+    }
+    previous = current;
+  }
+  previous[b.len()]
+}
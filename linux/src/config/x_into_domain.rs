@@ -12,6 +12,14 @@ impl<'a> mapper::config::IsIntoDomain<'a, XAppIdentifier, XKeySymbol, XModifier,
     Ok(Application::new(app.0))
   }
 
+  fn into_domain_application_pattern(
+    &self,
+    app: mapper::config::Application,
+  ) -> Result<mapper::ApplicationPattern<XAppIdentifier>, mapper::config::InvalidConfigError> {
+    mapper::ApplicationPattern::parse(app.0.clone())
+      .map_err(|_| mapper::config::InvalidConfigError::InvalidApplicationPattern(app.0))
+  }
+
   fn into_domain_keyinput(
     &self,
     key_input: mapper::config::KeyInput,
@@ -76,3 +84,70 @@ impl<'a> mapper::config::IsIntoDomain<'a, XAppIdentifier, XKeySymbol, XModifier,
       ))
   }
 }
+
+// 今のkeyboardの配列で、Shiftなどを押さないと出ないkeysymを元のkeyとmodifierに置き換える
+// 押されたkeyはlevelによらず最初のkeysymになるので、`question`は`Shift-slash`として扱う(XKeymapを参照)
+pub struct XLayoutIntoDomain {
+  key_resolver: XKeyResolver,
+}
+
+impl XLayoutIntoDomain {
+  // XEventSourceのものを渡すと、配列が変わったときに読み直した対応で変換する
+  pub fn new(key_resolver: XKeyResolver) -> Self {
+    Self { key_resolver }
+  }
+}
+
+impl<'a> mapper::config::IsIntoDomain<'a, XAppIdentifier, XKeySymbol, XModifier, XExecution>
+  for XLayoutIntoDomain
+{
+  fn into_domain_application(
+    &self,
+    app: mapper::config::Application,
+  ) -> Result<Application, mapper::config::InvalidConfigError> {
+    XIntoDomain.into_domain_application(app)
+  }
+
+  fn into_domain_application_pattern(
+    &self,
+    app: mapper::config::Application,
+  ) -> Result<mapper::ApplicationPattern<XAppIdentifier>, mapper::config::InvalidConfigError> {
+    XIntoDomain.into_domain_application_pattern(app)
+  }
+
+  fn into_domain_keyinput(
+    &self,
+    key_input: mapper::config::KeyInput,
+  ) -> Result<KeyInput, mapper::config::InvalidConfigError> {
+    let key_input = XIntoDomain.into_domain_keyinput(key_input)?;
+    Ok(self.key_resolver.normalize(key_input))
+  }
+
+  fn into_domain_action(
+    &self,
+    from: mapper::config::KeyInput,
+    execution: mapper::config::Execution,
+  ) -> Result<
+    Box<dyn mapper::KeyBind<XKeySymbol, XModifier, XExecution> + 'a>,
+    mapper::config::InvalidConfigError,
+  > {
+    Ok(Box::new(XExecutionKeyBind::new(
+      self.into_domain_keyinput(from)?,
+      execution.0,
+    )))
+  }
+
+  fn into_domain_key(
+    &self,
+    key: mapper::config::Key,
+  ) -> Result<crate::Key, mapper::config::InvalidConfigError> {
+    XIntoDomain.into_domain_key(key)
+  }
+
+  fn into_domain_modifier(
+    &self,
+    modifier: mapper::config::Modifier,
+  ) -> Result<crate::Modifier, mapper::config::InvalidConfigError> {
+    XIntoDomain.into_domain_modifier(modifier)
+  }
+}
@@ -0,0 +1,116 @@
+use std::ffi::{CString, OsStr, OsString};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+// 設定ファイルの書き換え(inotify)とSIGHUPを監視する
+pub struct ConfigWatcher {
+  inotify: RawFd,
+  file_name: OsString,
+  // SIGHUPを受け取ると書き込まれる
+  signal: UnixStream,
+}
+
+impl ConfigWatcher {
+  pub fn new(path: &Path) -> std::io::Result<Self> {
+    let invalid_path =
+      || std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid config path");
+    let file_name = path.file_name().ok_or_else(invalid_path)?.to_os_string();
+    // editorはrenameで保存することがあるので、ファイルではなくdirectoryを監視する
+    let dir = match path.parent() {
+      Some(dir) if !dir.as_os_str().is_empty() => dir,
+      _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_bytes()).map_err(|_| invalid_path())?;
+
+    let inotify = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if inotify < 0 {
+      return Err(std::io::Error::last_os_error());
+    }
+    let watcher = Self {
+      inotify,
+      file_name,
+      signal: Self::register_sighup()?,
+    };
+    let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+    if unsafe { libc::inotify_add_watch(inotify, dir.as_ptr(), mask) } < 0 {
+      return Err(std::io::Error::last_os_error());
+    }
+    Ok(watcher)
+  }
+
+  fn register_sighup() -> std::io::Result<UnixStream> {
+    let (reader, writer) = UnixStream::pair()?;
+    reader.set_nonblocking(true)?;
+    signal_hook::low_level::pipe::register(signal_hook::consts::SIGHUP, writer)?;
+    Ok(reader)
+  }
+
+  // pollで待つfd
+  pub fn fds(&self) -> Vec<RawFd> {
+    vec![self.inotify, self.signal.as_raw_fd()]
+  }
+
+  // 設定ファイルが書き換えられたか、SIGHUPを受け取ったか。溜まっている通知は読み捨てる
+  pub fn take_changed(&self) -> bool {
+    let signaled = self.drain_signal();
+    let modified = self.drain_inotify();
+    signaled || modified
+  }
+
+  fn drain_signal(&self) -> bool {
+    let mut buffer = [0u8; 64];
+    let mut signaled = false;
+    while let Ok(size) = (&self.signal).read(&mut buffer) {
+      if size == 0 {
+        break;
+      }
+      signaled = true;
+    }
+    signaled
+  }
+
+  fn drain_inotify(&self) -> bool {
+    let mut buffer = [0u8; 4096];
+    let mut modified = false;
+    loop {
+      let size = unsafe {
+        libc::read(
+          self.inotify,
+          buffer.as_mut_ptr() as *mut libc::c_void,
+          buffer.len(),
+        )
+      };
+      if size <= 0 {
+        return modified;
+      }
+      // inotify_eventの後ろに、NUL埋めされたlen byteのファイル名が続く
+      let header_size = std::mem::size_of::<libc::inotify_event>();
+      let mut offset = 0;
+      while offset + header_size <= size as usize {
+        let event = unsafe {
+          std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::inotify_event)
+        };
+        let name = &buffer[offset + header_size..offset + header_size + event.len as usize];
+        let name = match name.iter().position(|b| *b == 0) {
+          Some(end) => &name[..end],
+          None => name,
+        };
+        if OsStr::from_bytes(name) == self.file_name {
+          modified = true;
+        }
+        offset += header_size + event.len as usize;
+      }
+    }
+  }
+}
+
+impl Drop for ConfigWatcher {
+  fn drop(&mut self) {
+    unsafe {
+      libc::close(self.inotify);
+    }
+  }
+}
@@ -4,17 +4,62 @@ class Converter
   def initialize
     @global = {}
     @in_app = {}
+    @modes = {}
+    @windows = []
+    @chord_timeout = nil
+    @ignore_modifiers = []
+    @priority = { in_app: {}, modes: {} }
     @current_context = @global
   end
 
+  def chord_timeout(millis)
+    @chord_timeout = millis
+  end
+
+  def ignore_modifiers(*modifiers)
+    @ignore_modifiers.concat(modifiers.flatten)
+  end
+
   def execute(command)
     { execute: command }
   end
 
+  def enter_mode(mode)
+    { enter_mode: mode }
+  end
+
+  def exit_mode(mode)
+    { exit_mode: mode }
+  end
+
+  def toggle_mode(mode)
+    { toggle_mode: mode }
+  end
+
+  def tap_hold(tap:, hold:, timeout: nil)
+    { tap: tap, hold: hold, hold_timeout: timeout }.compact
+  end
+
+  def disable
+    { disable: true }
+  end
+
+  def global_priority(value)
+    @priority[:remap] = value
+  end
+
+  def delay(key, millis)
+    { to: key, delay: millis }
+  end
+
   def remap(key, **arguments)
     case arguments
     in { to: { execute: execute } }
       @current_context.merge!(_remap_execution(key, execute: execute))
+    in { to: Array => sequence }
+      @current_context.merge!(key => { sequence: sequence })
+    in { to: Hash => action }
+      @current_context.merge!(key => action)
     in { to: to, with_modifier: with }
       @current_context.merge!(_remap_key(key, to: to, with: Array(with)))
     in { to: to }
@@ -24,8 +69,17 @@ class Converter
     end
   end
 
-  def window(class_only:)
+  def window(class_only: nil, class_not: nil, title_only: nil, title_not: nil, priority: nil, &block)
+    class_only = class_only && Array(class_only).map { |pattern| _application_pattern(pattern) }
+    class_not = class_not && Array(class_not).map { |pattern| _application_pattern(pattern) }
+    title_only = title_only && Array(title_only).map { |pattern| _application_pattern(pattern) }
+    title_not = title_not && Array(title_not).map { |pattern| _application_pattern(pattern) }
+    if class_not || title_only || title_not
+      return _window_matching(class_only, class_not, title_only, title_not, priority, &block)
+    end
+
     Array(class_only).each do |class_only|
+      @priority[:in_app][class_only] = priority if priority
       @in_app[class_only] ||= {}
       @current_context = @in_app[class_only]
       yield
@@ -33,13 +87,46 @@ class Converter
     end
   end
 
+  def mode(name, priority: nil)
+    @priority[:modes][name] = priority if priority
+    @modes[name] ||= {}
+    @current_context = @modes[name]
+    yield
+    @current_context = @global
+  end
+
   def to_json
     JSON.generate(
       remap: @global,
       in_app: @in_app,
+      modes: @modes,
+      chord_timeout: @chord_timeout,
+      ignore_modifiers: @ignore_modifiers,
+      priority: @priority,
+      windows: @windows,
     )
   end
 
+  def _window_matching(class_only, class_not, title_only, title_not, priority)
+    window = {
+      class_only: class_only && Array(class_only),
+      class_not: Array(class_not),
+      title_only: title_only && Array(title_only),
+      title_not: Array(title_not),
+      remap: {},
+      priority: priority,
+    }.compact
+    @windows << window
+    @current_context = window[:remap]
+    yield
+    @current_context = @global
+  end
+
+  # Regexp は `/.../` の文字列として渡す
+  def _application_pattern(pattern)
+    pattern.is_a?(Regexp) ? "/#{pattern.source}/" : pattern
+  end
+
   def _remap_key(key, to:, with:)
     {
       key => { to: to, with: with }
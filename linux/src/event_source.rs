@@ -1,116 +1,283 @@
 use super::*;
 use mapper::IsEventSource;
-use std::ffi::CString;
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+use std::io::Read;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::time::Instant;
 use x11::xlib;
 
+const XKB_MAJOR_VERSION: i32 = 1;
+const XKB_MINOR_VERSION: i32 = 0;
+// XkbStateNotifyのうち、modifierとgroupが変わったことを表すbit
+const XKB_MODIFIER_STATE_MASK: libc::c_ulong = 1;
+const XKB_GROUP_STATE_MASK: libc::c_ulong = 1 << 4;
+
 pub struct XEventSource {
   display: XDisplay,
+  // titleの変更を監視しているwindow
+  focused_window: Cell<xlib::Window>,
+  // 最後に通知した_NET_ACTIVE_WINDOW。window managerが設定しなければ0
+  active_window: Cell<xlib::Window>,
+  // grabするkeyがなくてもactive windowの変更を受け取れるよう、最初のeventを待つ前にrootを監視する
+  root_watched: Cell<bool>,
+  // XKBのeventのtype。XKBが使えなければNone
+  xkb_event_base: Cell<Option<i32>>,
+  config_watcher: Option<ConfigWatcher>,
+  // SIGINTかSIGTERMを受け取ると書き込まれる
+  shutdown_signal: Option<UnixStream>,
+  grabber: Rc<XGrabber>,
+  key_resolver: XKeyResolver,
 }
 
 impl IsEventSource<XKeySymbol, XModifier, XAppIdentifier> for XEventSource {
-  fn ungrab_keys(&self) {
-    unsafe {
-      xlib::XUngrabKey(
-        self.display,
-        xlib::AnyKey,
-        xlib::AnyModifier,
-        xlib::XDefaultRootWindow(self.display),
-      );
+  fn ungrab_keys(&self, grabs: Vec<Grab>) {
+    self.grabber.ungrab_keys(grabs);
+  }
+
+  fn grab_keys(&self, grabs: Vec<Grab>) {
+    self.grabber.grab_keys(grabs);
+  }
+
+  fn next(&self) -> Option<Event> {
+    self.wait_event(None)
+  }
+
+  fn next_until(&self, deadline: Instant) -> Option<Event> {
+    self.wait_event(Some(deadline))
+  }
+
+  fn grab_keyboard(&self) {
+    self.grabber.grab_keyboard();
+  }
+
+  fn ungrab_keyboard(&self) {
+    self.grabber.ungrab_keyboard();
+  }
+}
+
+impl XEventSource {
+  pub fn new(display: XDisplay) -> Self {
+    let key_resolver = XKeyResolver::new(display);
+    Self {
+      display,
+      focused_window: Cell::new(0),
+      active_window: Cell::new(0),
+      root_watched: Cell::new(false),
+      xkb_event_base: Cell::new(None),
+      config_watcher: None,
+      shutdown_signal: None,
+      grabber: Rc::new(XGrabber::new(display, key_resolver.clone())),
+      key_resolver,
     }
   }
 
-  fn grab_keys(&self, key_inputs: Vec<KeyInput>) {
-    let display = self.display;
-    unsafe {
-      for key_input in key_inputs {
-        let key = key_input.key().raw_value();
-        let modifiers = key_input
-          .modifiers()
-          .to_vec()
-          .into_iter()
-          .fold(0, |sum, modifier| sum | modifier.raw_value());
-        xlib::XGrabKey(
-          display,
-          xlib::XKeysymToKeycode(display, key) as i32,
-          modifiers,
-          xlib::XDefaultRootWindow(display),
-          xlib::True,
-          xlib::GrabModeAsync,
-          xlib::GrabModeAsync,
-        );
+  // XTestKeyHandlerと共有する
+  pub fn grabber(&self) -> Rc<XGrabber> {
+    self.grabber.clone()
+  }
+
+  // key handlerや設定の変換と共有し、配列が変わったらここで作り直す
+  pub fn key_resolver(&self) -> XKeyResolver {
+    self.key_resolver.clone()
+  }
+
+  // 設定ファイルが書き換えられたらEvent::Reloadを返すようにする
+  pub fn with_config_watcher(mut self, config_watcher: ConfigWatcher) -> Self {
+    self.config_watcher = Some(config_watcher);
+    self
+  }
+
+  // SIGINTかSIGTERMを受け取ったらnextがNoneを返すようにする。Stateは押したままのkeyを離してから終了する
+  pub fn with_shutdown_signals(mut self) -> std::io::Result<Self> {
+    let (reader, writer) = UnixStream::pair()?;
+    reader.set_nonblocking(true)?;
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+      signal_hook::low_level::pipe::register(signal, writer.try_clone()?)?;
+    }
+    self.shutdown_signal = Some(reader);
+    Ok(self)
+  }
+
+  fn is_shutting_down(&self) -> bool {
+    let mut buffer = [0u8; 64];
+    match &self.shutdown_signal {
+      Some(shutdown_signal) => {
+        matches!((&*shutdown_signal).read(&mut buffer), Ok(size) if size > 0)
       }
-      xlib::XSelectInput(
-        display,
-        xlib::XDefaultRootWindow(display),
-        xlib::KeyPressMask | xlib::KeyReleaseMask | xlib::PropertyChangeMask,
-      );
+      None => false,
     }
   }
 
-  fn next(&self) -> Option<Event> {
+  // deadlineがなければeventが来るまで待ち続ける
+  fn wait_event(&self, deadline: Option<Instant>) -> Option<Event> {
     let mut event: xlib::XEvent = xlib::XEvent { type_: 0 };
+    if let Some(event) = self.watch_root() {
+      return Some(event);
+    }
 
     loop {
+      if self.is_shutting_down() {
+        return None;
+      }
       unsafe {
-        xlib::XNextEvent(self.display, &mut event);
-        match event {
-          xlib::XEvent {
-            type_: xlib::KeyPress,
-          } => {
-            let x_key_sym = xlib::XKeycodeToKeysym(self.display, event.key.keycode as u8, 0);
-            let key = Key::new(x_key_sym);
-
-            let modifier_bitmap: u32 = event.key.state;
-            let mut modifiers = vec![];
-            for i in 0..=31 {
-              let mask = 1 << i;
-              if (modifier_bitmap & mask) > 0 {
-                modifiers.push(Modifier::new(mask))
-              }
-            }
-
-            return Some(Event::KeyPressed {
-              key_input: KeyInput::new(key, Modifiers::new(modifiers)),
-            });
-          }
-          xlib::XEvent {
-            type_: xlib::KeyRelease,
-          } => {
-            let x_key_sym = xlib::XKeycodeToKeysym(self.display, event.key.keycode as u8, 0);
-            let key = Key::new(x_key_sym);
-
-            let modifier_bitmap: u32 = event.key.state;
-            let mut modifiers = vec![];
-            for i in 0..=31 {
-              let mask = 1 << i;
-              if (modifier_bitmap & mask) > 0 {
-                modifiers.push(Modifier::new(mask))
-              }
-            }
-
-            return Some(Event::KeyReleased {
-              key_input: KeyInput::new(key, Modifiers::new(modifiers)),
-            });
+        while xlib::XPending(self.display) > 0 {
+          xlib::XNextEvent(self.display, &mut event);
+          if let Some(converted) = self.convert_event(event) {
+            return Some(converted);
           }
-          xlib::XEvent {
-            type_: xlib::PropertyNotify,
-          } => {
-            let application = self.fetch_focused_application();
-            log::info!("{:?}", application);
-            return Some(Event::ApplicationChanged {
-              next_application: application,
-            });
+        }
+      }
+
+      if let Some(config_watcher) = &self.config_watcher {
+        if config_watcher.take_changed() {
+          return Some(Event::Reload);
+        }
+      }
+
+      let timeout = match deadline {
+        Some(deadline) => {
+          let now = Instant::now();
+          if deadline <= now {
+            return Some(Event::Tick);
           }
-          _ => {}
+          (deadline - now).as_millis() as i32 + 1
         }
+        None => -1,
+      };
+      // X serverとの接続か設定ファイルの監視にデータが届くか、deadlineを過ぎるまで待つ
+      let mut pollfds: Vec<libc::pollfd> =
+        std::iter::once(unsafe { xlib::XConnectionNumber(self.display) })
+          .chain(
+            self
+              .config_watcher
+              .iter()
+              .flat_map(|config_watcher| config_watcher.fds()),
+          )
+          .chain(
+            self
+              .shutdown_signal
+              .iter()
+              .map(|shutdown_signal| shutdown_signal.as_raw_fd()),
+          )
+          .map(|fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+          })
+          .collect();
+      unsafe {
+        libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout);
       }
     }
   }
-}
 
-impl XEventSource {
-  pub fn new(display: XDisplay) -> Self {
-    Self { display }
+  fn convert_event(&self, event: xlib::XEvent) -> Option<Event> {
+    unsafe {
+      match event {
+        xlib::XEvent {
+          type_: xlib::KeyPress,
+        } => Some(Event::KeyPressed {
+          key_input: self.key_input(event.key),
+        }),
+        xlib::XEvent {
+          type_: xlib::KeyRelease,
+        } => {
+          // withのmodifierを先に離していたら、ここでkeyboardのgrabを外す
+          self.grabber.update_optional_grab();
+          Some(Event::KeyReleased {
+            key_input: self.key_input(event.key),
+          })
+        }
+        xlib::XEvent {
+          type_: xlib::PropertyNotify,
+        } if event.property.window != xlib::XDefaultRootWindow(self.display) => {
+          // focusしているwindowのtitleが変わったときだけ通知する
+          if event.property.window != self.focused_window.get()
+            || !self.is_title_atom(event.property.atom)
+          {
+            return None;
+          }
+          let application = self.fetch_focused_application();
+          log::info!("{:?}", application);
+          Some(Event::ApplicationChanged {
+            next_application: application,
+          })
+        }
+        xlib::XEvent {
+          type_: xlib::PropertyNotify,
+        } => {
+          // rootのpropertyは時計などでも変わるので、active windowが別のwindowになったときだけ通知する
+          if event.property.atom != self.net_active_window_atom() {
+            return None;
+          }
+          let active_window = self.fetch_active_window();
+          if active_window == self.active_window.get() {
+            return None;
+          }
+          self.active_window.set(active_window);
+          let application = self.fetch_focused_application();
+          log::info!("{:?}", application);
+          Some(Event::ApplicationChanged {
+            next_application: application,
+          })
+        }
+        xlib::XEvent {
+          type_: xlib::FocusOut,
+        } => {
+          // _NET_ACTIVE_WINDOWを設定しないwindow managerでは、focusしていたwindowからfocusが外れたら通知する
+          // keyboardのgrabや、子windowへfocusが移っただけのときは通知しない
+          let focus_change = event.focus_change;
+          if self.active_window.get() != 0
+            || focus_change.window != self.focused_window.get()
+            || focus_change.mode == xlib::NotifyGrab
+            || focus_change.mode == xlib::NotifyUngrab
+            || focus_change.detail == xlib::NotifyInferior
+          {
+            return None;
+          }
+          let application = self.fetch_focused_application();
+          log::info!("{:?}", application);
+          Some(Event::ApplicationChanged {
+            next_application: application,
+          })
+        }
+        xlib::XEvent {
+          type_: xlib::MappingNotify,
+        } => {
+          let mut mapping = event.mapping;
+          xlib::XRefreshKeyboardMapping(&mut mapping);
+          // pointerのbuttonの配列が変わってもgrabし直さなくてよい
+          if mapping.request == xlib::MappingPointer {
+            return None;
+          }
+          self.key_resolver.refresh();
+          log::info!("keyboard mapping changed");
+          Some(Event::KeymapChanged)
+        }
+        xlib::XEvent { type_ } if Some(type_) == self.xkb_event_base.get() => {
+          let xkb_event = &*(&event as *const xlib::XEvent as *const xlib::XkbAnyEvent);
+          if xkb_event.xkb_type == xlib::XkbStateNotify {
+            let state_event = &*(&event as *const xlib::XEvent as *const xlib::XkbStateNotifyEvent);
+            self.key_resolver.set_group(state_event.group);
+            self.grabber.set_held_modifiers(state_event.mods);
+            return None;
+          }
+          if xkb_event.xkb_type != xlib::XkbNewKeyboardNotify
+            && xkb_event.xkb_type != xlib::XkbMapNotify
+          {
+            return None;
+          }
+          self.refresh_keyboard_mapping();
+          self.key_resolver.refresh();
+          log::info!("keyboard layout changed");
+          Some(Event::KeymapChanged)
+        }
+        _ => None,
+      }
+    }
   }
 
   fn fetch_focused_application(&self) -> Option<Application> {
@@ -118,6 +285,10 @@ impl XEventSource {
       let mut focused_window = 0;
       let mut focus_state = 0;
       xlib::XGetInputFocus(self.display, &mut focused_window, &mut focus_state);
+      // focusがNoneかPointerRootのときは、focusしているapplicationはない
+      if focused_window <= xlib::PointerRoot as xlib::Window {
+        return None;
+      }
 
       let class_atom = xlib::XInternAtom(
         self.display,
@@ -172,13 +343,19 @@ impl XEventSource {
         target_window = parent;
       }
 
+      self.watch_title(target_window);
+      let with_title = |application: Application| match self.fetch_title(target_window) {
+        Some(title) => application.with_title(title),
+        None => application,
+      };
+
       if x_text_property.nitems > 0 && !x_text_property.value.is_null() {
         if x_text_property.encoding == xlib::XA_STRING {
-          Some(Application::new(
+          Some(with_title(Application::new(
             CString::from_raw(x_text_property.value as *mut i8)
               .into_string()
               .unwrap(),
-          ))
+          )))
         } else {
           let mut char_list: *mut *mut i8 = std::ptr::null_mut();
           let mut count: i32 = 0;
@@ -194,11 +371,217 @@ impl XEventSource {
             String::from("")
           };
           xlib::XFreeStringList(char_list);
-          Some(Application::new(name))
+          Some(with_title(Application::new(name)))
         }
       } else {
         None
       }
     }
   }
+
+  // titleが変わったときにPropertyNotifyを、focusが外れたときにFocusOutを受け取れるようにする
+  fn watch_title(&self, window: xlib::Window) {
+    if self.focused_window.get() == window {
+      return;
+    }
+    unsafe {
+      xlib::XSelectInput(
+        self.display,
+        window,
+        xlib::PropertyChangeMask | xlib::FocusChangeMask,
+      );
+    }
+    self.focused_window.set(window);
+  }
+
+  // 最初に呼ばれたときは、起動時にfocusしているapplicationを返す
+  fn watch_root(&self) -> Option<Event> {
+    if self.root_watched.replace(true) {
+      return None;
+    }
+    unsafe {
+      xlib::XSelectInput(
+        self.display,
+        xlib::XDefaultRootWindow(self.display),
+        xlib::KeyPressMask | xlib::KeyReleaseMask | xlib::PropertyChangeMask,
+      );
+    }
+    self.watch_keyboard_mapping();
+    // _NET_ACTIVE_WINDOWがなければ0のままになり、focusしているwindowから外れたときに通知する
+    self.active_window.set(self.fetch_active_window());
+    let application = self.fetch_focused_application();
+    log::info!("{:?}", application);
+    Some(Event::ApplicationChanged {
+      next_application: application,
+    })
+  }
+
+  fn key_input(&self, event: xlib::XKeyEvent) -> KeyInput {
+    self
+      .key_resolver
+      .key_input(event.keycode as u8, event.state)
+  }
+
+  // setxkbmapや別の配列のkeyboardをつないだときに通知を受け取る
+  fn watch_keyboard_mapping(&self) {
+    let (mut opcode, mut event_base, mut error_base) = (0, 0, 0);
+    let (mut major, mut minor) = (XKB_MAJOR_VERSION, XKB_MINOR_VERSION);
+    unsafe {
+      if xlib::XkbQueryExtension(
+        self.display,
+        &mut opcode,
+        &mut event_base,
+        &mut error_base,
+        &mut major,
+        &mut minor,
+      ) == xlib::False
+      {
+        log::warn!(
+          "XKB is not available, keyboard layout changes are notified only by MappingNotify and keymaps with `with` match only without those modifiers"
+        );
+        return;
+      }
+      let mask = xlib::XkbNewKeyboardNotifyMask | xlib::XkbMapNotifyMask;
+      xlib::XkbSelectEvents(self.display, XKB_USE_CORE_KBD, mask, mask);
+      // keyを送るgroupと、withのmodifierが押されているかを決めるのに使う
+      let details = XKB_MODIFIER_STATE_MASK | XKB_GROUP_STATE_MASK;
+      xlib::XkbSelectEventDetails(
+        self.display,
+        XKB_USE_CORE_KBD,
+        xlib::XkbStateNotify as u32,
+        details,
+        details,
+      );
+    }
+    self.xkb_event_base.set(Some(event_base));
+  }
+
+  // Xlibが覚えているkeycodeとkeysymの対応を読み直させる
+  fn refresh_keyboard_mapping(&self) {
+    let (mut min_keycode, mut max_keycode) = (0, 0);
+    unsafe {
+      xlib::XDisplayKeycodes(self.display, &mut min_keycode, &mut max_keycode);
+      let mut mapping = xlib::XMappingEvent {
+        type_: xlib::MappingNotify,
+        serial: 0,
+        send_event: xlib::False,
+        display: self.display,
+        event: 0,
+        request: xlib::MappingKeyboard,
+        first_keycode: min_keycode,
+        count: max_keycode - min_keycode + 1,
+      };
+      xlib::XRefreshKeyboardMapping(&mut mapping);
+    }
+  }
+
+  fn net_active_window_atom(&self) -> xlib::Atom {
+    unsafe {
+      xlib::XInternAtom(
+        self.display,
+        CString::new("_NET_ACTIVE_WINDOW").unwrap().as_ptr(),
+        xlib::True,
+      )
+    }
+  }
+
+  // window managerがなければ0を返す
+  fn fetch_active_window(&self) -> xlib::Window {
+    let atom = self.net_active_window_atom();
+    if atom == 0 {
+      return 0;
+    }
+    unsafe {
+      let mut actual_type = 0;
+      let mut actual_format = 0;
+      let mut nitems = 0;
+      let mut bytes_after = 0;
+      let mut value: *mut u8 = std::ptr::null_mut();
+      let status = xlib::XGetWindowProperty(
+        self.display,
+        xlib::XDefaultRootWindow(self.display),
+        atom,
+        0,
+        1,
+        xlib::False,
+        xlib::XA_WINDOW,
+        &mut actual_type,
+        &mut actual_format,
+        &mut nitems,
+        &mut bytes_after,
+        &mut value,
+      );
+      if value.is_null() {
+        return 0;
+      }
+      let window = if status == xlib::Success as i32 && nitems > 0 && actual_format == 32 {
+        // format 32のpropertyはlongの配列で返る
+        *(value as *mut xlib::Window)
+      } else {
+        0
+      };
+      xlib::XFree(value as *mut std::ffi::c_void);
+      window
+    }
+  }
+
+  fn is_title_atom(&self, atom: xlib::Atom) -> bool {
+    atom == xlib::XA_WM_NAME || atom == self.net_wm_name_atom()
+  }
+
+  fn net_wm_name_atom(&self) -> xlib::Atom {
+    unsafe {
+      xlib::XInternAtom(
+        self.display,
+        CString::new("_NET_WM_NAME").unwrap().as_ptr(),
+        xlib::True,
+      )
+    }
+  }
+
+  // _NET_WM_NAME(UTF-8)を優先し、なければWM_NAMEを使う
+  fn fetch_title(&self, window: xlib::Window) -> Option<String> {
+    let net_wm_name = self.net_wm_name_atom();
+    for atom in [net_wm_name, xlib::XA_WM_NAME].iter() {
+      if *atom == 0 {
+        continue;
+      }
+      unsafe {
+        let mut x_text_property = xlib::XTextProperty {
+          encoding: 0,
+          nitems: 0,
+          format: 0,
+          value: std::ptr::null_mut(),
+        };
+        if xlib::XGetTextProperty(self.display, window, &mut x_text_property, *atom) == 0
+          || x_text_property.value.is_null()
+        {
+          continue;
+        }
+
+        let mut char_list: *mut *mut i8 = std::ptr::null_mut();
+        let mut count: i32 = 0;
+        let result = xlib::Xutf8TextPropertyToTextList(
+          self.display,
+          &x_text_property,
+          &mut char_list,
+          &mut count,
+        );
+        xlib::XFree(x_text_property.value as *mut std::ffi::c_void);
+        if char_list.is_null() {
+          continue;
+        }
+        let title = if result >= 0 && count > 0 && !(*char_list).is_null() {
+          Some(CStr::from_ptr(*char_list).to_string_lossy().into_owned())
+        } else {
+          None
+        };
+        xlib::XFreeStringList(char_list);
+        if title.is_some() {
+          return title;
+        }
+      }
+    }
+    None
+  }
 }
@@ -1,5 +1,5 @@
 use super::{KeyInput, XExecution, XKeySymbol, XModifier};
-use mapper::{Action, KeyBind};
+use mapper::{Action, KeyBind, Trigger};
 
 #[derive(Debug, Clone)]
 pub struct XExecutionKeyBind {
@@ -30,4 +30,8 @@ impl KeyBind<XKeySymbol, XModifier, XExecution> for XExecutionKeyBind {
   fn released(&self, _key_input: &KeyInput) -> Option<Action<XKeySymbol, XModifier, XExecution>> {
     None
   }
+
+  fn triggers(&self) -> Vec<Trigger<XKeySymbol, XModifier>> {
+    vec![Trigger::exact(self.from.clone())]
+  }
 }
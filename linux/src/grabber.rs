@@ -0,0 +1,237 @@
+use super::*;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use x11::xlib;
+
+// grabした入力と、grabしたときのkeycode
+type GrabbedKey = (Grab, u8);
+
+// X serverへのgrabをまとめて扱う
+// XTestで送ったkeyを自分のgrabで横取りしないよう、XEventSourceとXTestKeyHandlerで共有する
+pub struct XGrabber {
+  display: XDisplay,
+  key_resolver: XKeyResolver,
+  // grab_keysでgrabしている入力
+  // keyboardの配列が変わってもgrabしたときのkeycodeで外せるように覚えておく
+  grabs: RefCell<Vec<GrabbedKey>>,
+  // Stateがkeyboardをgrabしているか
+  keyboard_grabbed: Cell<bool>,
+  // withのmodifierが押されているのでkeyboardをgrabしているか
+  // withの組み合わせはgrabしないので、押されている間はkeyboardごと横取りしてStateでふるい分ける
+  optional_grabbed: Cell<bool>,
+  // XkbStateNotifyで受け取った、今押されているmodifier
+  held_modifiers: Cell<u32>,
+  // XTestで押したまま、まだ離していないkeycode
+  injected_keys: RefCell<BTreeSet<u8>>,
+}
+
+impl XGrabber {
+  pub fn new(display: XDisplay, key_resolver: XKeyResolver) -> Self {
+    Self {
+      display,
+      key_resolver,
+      grabs: RefCell::new(vec![]),
+      keyboard_grabbed: Cell::new(false),
+      optional_grabbed: Cell::new(false),
+      held_modifiers: Cell::new(0),
+      injected_keys: RefCell::new(BTreeSet::new()),
+    }
+  }
+
+  pub fn grab_keys(&self, grabs: Vec<Grab>) {
+    let grabs: Vec<GrabbedKey> = grabs
+      .into_iter()
+      .map(|grab| {
+        let keycode = self.keycode(&grab);
+        (grab, keycode)
+      })
+      .collect();
+    self.x_grab_keys(&grabs);
+    self.grabs.borrow_mut().extend(grabs);
+  }
+
+  pub fn ungrab_keys(&self, grabs: Vec<Grab>) {
+    let (ungrabbed, kept): (Vec<GrabbedKey>, Vec<GrabbedKey>) = self
+      .grabs
+      .take()
+      .into_iter()
+      .partition(|(grab, _)| grabs.contains(grab));
+    self.x_ungrab_keys(&ungrabbed);
+    self.grabs.replace(kept);
+  }
+
+  pub fn grab_keyboard(&self) {
+    self.keyboard_grabbed.set(true);
+    self.x_grab_keyboard();
+  }
+
+  // XTestで押しているkeyがあれば、その離す入力を受け取れるようgrabしたままにする
+  pub fn ungrab_keyboard(&self) {
+    self.keyboard_grabbed.set(false);
+    if !self.keeps_keyboard() {
+      unsafe {
+        xlib::XUngrabKeyboard(self.display, xlib::CurrentTime);
+      }
+    }
+  }
+
+  // modifierが変わるたびに呼ぶ
+  pub fn set_held_modifiers(&self, modifiers: u32) {
+    self.held_modifiers.set(modifiers);
+    self.update_optional_grab();
+  }
+
+  // withのkeyのmodifierとwithのmodifierが押されている間だけkeyboardをgrabする
+  // modifierを離しても、横取りしたkeyが押されたままなら離す入力を受け取るまでgrabしておく
+  pub fn update_optional_grab(&self) {
+    let held = self.held_modifiers.get();
+    let needed = self
+      .grabs
+      .borrow()
+      .iter()
+      .any(|(grab, _)| holds_optional_modifiers(grab, held));
+    if needed == self.optional_grabbed.get() {
+      return;
+    }
+    if needed {
+      self.optional_grabbed.set(true);
+      self.x_grab_keyboard();
+    } else if !self.grabbed_key_held() {
+      self.optional_grabbed.set(false);
+      if !self.keeps_keyboard() {
+        unsafe {
+          xlib::XUngrabKeyboard(self.display, xlib::CurrentTime);
+        }
+      }
+    }
+  }
+
+  // keycodesのgrabとkeyboardのgrabを外している間にinjectを実行する
+  // 外している間に送ったkeyは、grabに横取りされずにfocusしているwindowに届く
+  pub fn without_grabs(&self, keycodes: &[u8], inject: impl FnOnce()) {
+    let grabs: Vec<GrabbedKey> = self
+      .grabs
+      .borrow()
+      .iter()
+      .filter(|(_, keycode)| keycodes.contains(keycode))
+      .cloned()
+      .collect();
+    self.x_ungrab_keys(&grabs);
+    unsafe {
+      xlib::XUngrabKeyboard(self.display, xlib::CurrentTime);
+    }
+    inject();
+    self.x_grab_keys(&grabs);
+    // 押したkeyを離す入力も受け取れるよう、押している間はkeyboardをgrabする
+    if self.keeps_keyboard() {
+      self.x_grab_keyboard();
+    }
+  }
+
+  fn keeps_keyboard(&self) -> bool {
+    self.keyboard_grabbed.get()
+      || self.optional_grabbed.get()
+      || !self.injected_keys.borrow().is_empty()
+  }
+
+  // grabしているkeyのどれかが押されているか
+  fn grabbed_key_held(&self) -> bool {
+    let mut keys = [0 as libc::c_char; 32];
+    unsafe {
+      xlib::XQueryKeymap(self.display, keys.as_mut_ptr());
+    }
+    self
+      .grabs
+      .borrow()
+      .iter()
+      .any(|(_, keycode)| keys[*keycode as usize / 8] as u8 & (1 << (keycode % 8)) != 0)
+  }
+
+  pub fn set_injected(&self, keycode: u8, pressed: bool) {
+    let mut injected_keys = self.injected_keys.borrow_mut();
+    if pressed {
+      injected_keys.insert(keycode);
+    } else {
+      injected_keys.remove(&keycode);
+    }
+  }
+
+  fn keycode(&self, grab: &Grab) -> u8 {
+    self.key_resolver.keycode(grab.key().raw_value())
+  }
+
+  fn x_grab_keys(&self, grabs: &[GrabbedKey]) {
+    let root = unsafe { xlib::XDefaultRootWindow(self.display) };
+    for (grab, keycode) in grabs {
+      for modifiers in grab_modifiers(grab) {
+        unsafe {
+          xlib::XGrabKey(
+            self.display,
+            *keycode as i32,
+            modifiers,
+            root,
+            xlib::True,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+          );
+        }
+      }
+    }
+  }
+
+  fn x_ungrab_keys(&self, grabs: &[GrabbedKey]) {
+    let root = unsafe { xlib::XDefaultRootWindow(self.display) };
+    for (grab, keycode) in grabs {
+      for modifiers in grab_modifiers(grab) {
+        unsafe {
+          xlib::XUngrabKey(self.display, *keycode as i32, modifiers, root);
+        }
+      }
+    }
+  }
+
+  fn x_grab_keyboard(&self) {
+    unsafe {
+      xlib::XGrabKeyboard(
+        self.display,
+        xlib::XDefaultRootWindow(self.display),
+        xlib::True,
+        xlib::GrabModeAsync,
+        xlib::GrabModeAsync,
+        xlib::CurrentTime,
+      );
+    }
+  }
+}
+
+// grabで横取りするmodifierの組み合わせ
+// AnyModifierでgrabすると、ほかのclientのgrabとぶつかったときにBadAccessになるので使わない
+// withのmodifierが押されているときはkeyboardごとgrabするので、ここでは無視するmodifierの組み合わせだけにする
+pub fn grab_modifiers(grab: &Grab) -> Vec<u32> {
+  let modifiers = modifier_bits(&grab.key_input.modifiers());
+  let ignored = modifier_bits(&grab.ignored_modifiers);
+  let mut combinations = vec![];
+  let mut subset = ignored;
+  loop {
+    combinations.push(modifiers | subset);
+    if subset == 0 {
+      return combinations;
+    }
+    subset = (subset - 1) & ignored;
+  }
+}
+
+// 無視するmodifierを除いて、keyのmodifierとwithのmodifierのいくつかが押されているか
+pub fn holds_optional_modifiers(grab: &Grab, held: u32) -> bool {
+  let held = held & !modifier_bits(&grab.ignored_modifiers);
+  let modifiers = modifier_bits(&grab.key_input.modifiers());
+  let optional = modifier_bits(&grab.optional_modifiers);
+  held != modifiers && held & modifiers == modifiers && held & !(modifiers | optional) == 0
+}
+
+pub fn modifier_bits(modifiers: &Modifiers) -> u32 {
+  modifiers
+    .to_vec()
+    .into_iter()
+    .fold(0, |sum, modifier| sum | modifier.raw_value())
+}
@@ -4,6 +4,7 @@ use x11::xlib;
 
 pub struct XKeyHandler {
   display: XDisplay,
+  key_resolver: XKeyResolver,
 }
 
 impl IsKeyHandler<XKeySymbol, XModifier> for XKeyHandler {
@@ -17,11 +18,21 @@ impl IsKeyHandler<XKeySymbol, XModifier> for XKeyHandler {
 }
 
 impl XKeyHandler {
-  pub fn new(display: XDisplay) -> Self {
-    Self { display }
+  pub fn new(display: XDisplay, key_resolver: XKeyResolver) -> Self {
+    Self {
+      display,
+      key_resolver,
+    }
   }
 
   fn key_event(&self, display: *mut xlib::Display, key_input: KeyInput, evt_type: i32) {
+    let position = match self.key_resolver.position(key_input.key().raw_value()) {
+      Some(position) => position,
+      None => {
+        log::warn!("no keycode for {:?}", key_input.key());
+        return;
+      }
+    };
     unsafe {
       let mut focused_window = 0;
       let mut focus_state = 0;
@@ -44,8 +55,9 @@ impl XKeyHandler {
         y: 1,
         x_root: 1,
         y_root: 1,
-        state: modifier_bits,
-        keycode: xlib::XKeysymToKeycode(display, key_input.key().raw_value()) as u32,
+        // keysymが別のgroupにしかなければそのgroupで、Shiftなどが要るならそれも押したことにする
+        state: with_group(modifier_bits | position.modifiers, position.group),
+        keycode: position.keycode as u32,
         same_screen: xlib::True,
       };
       let event = xlib::XEvent { key: key_event };
@@ -0,0 +1,268 @@
+use super::*;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use x11::xlib;
+
+pub const XKB_USE_CORE_KBD: u32 = 0x0100;
+// XKBのgroupが入るstateのbit
+const GROUP_MASK: u32 = 0x6000;
+const GROUP_SHIFT: u32 = 13;
+const MAX_GROUPS: i32 = 4;
+const MAX_LEVELS: i32 = 8;
+// levelを上げるmodifier。Shift、ISO_Level3_Shift(Mod5)、ISO_Level5_Shift(Mod3)の順に少ない組み合わせから試す
+const LEVEL_MODIFIERS: [u32; 8] = [
+  0,
+  xlib::ShiftMask,
+  xlib::Mod5Mask,
+  xlib::Mod3Mask,
+  xlib::ShiftMask | xlib::Mod5Mask,
+  xlib::ShiftMask | xlib::Mod3Mask,
+  xlib::Mod5Mask | xlib::Mod3Mask,
+  xlib::ShiftMask | xlib::Mod5Mask | xlib::Mod3Mask,
+];
+
+// keysymを入力できるkeyと、そのkeysymが出るgroupとlevel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XKeyPosition {
+  pub keycode: u8,
+  pub group: i32,
+  pub level: i32,
+  // そのlevelにするためにkeyと一緒に押すmodifier
+  // NumLockでしか出ないkeypadのkeysymなどは0にして、levelを上げずに送る
+  pub modifiers: u32,
+}
+
+// XKBの配列を読んだもの。押されたkeyの解釈と、keysymを入力できるkeyの検索はこれを引くだけにする
+//
+// 押されたkeyは、modifierによらずそのgroupの最初のlevelのkeysymにして、modifierはKeyInputにそのまま残す
+// `exclam`のようにShiftやAltGrで出すkeysymは、設定を読むときにnormalizeで最初のlevelのkeysymとmodifierに直すので、
+// 押されたkeyのlevelをXkbLookupKeySymで決めなくても同じKeyInputになる
+// levelを決めてmodifierを消費させると、`Shift-slash`のように書いた設定が押されたkeyと合わなくなる
+#[derive(Debug, Clone, Default)]
+pub struct XKeymap {
+  // keycodeとgroupごとの、levelの順のkeysym
+  keys: BTreeMap<(u8, i32), Vec<XKeySymbol>>,
+  // keysymごとの、それを入力できるkeyの候補。modifierで出せないものはfalseにする
+  positions: BTreeMap<XKeySymbol, Vec<(bool, XKeyPosition)>>,
+}
+
+impl XKeymap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // keyのgroupの、levelの順のkeysymと、そのlevelにするmodifier。modifierで出せないlevelはNone
+  pub fn with_key(
+    mut self,
+    keycode: u8,
+    group: i32,
+    levels: Vec<(XKeySymbol, Option<u32>)>,
+  ) -> Self {
+    for (level, (keysym, modifiers)) in levels.iter().enumerate() {
+      if *keysym == 0 {
+        continue;
+      }
+      self.positions.entry(*keysym).or_default().push((
+        modifiers.is_some(),
+        XKeyPosition {
+          keycode,
+          group,
+          level: level as i32,
+          modifiers: modifiers.unwrap_or(0),
+        },
+      ));
+    }
+    self.keys.insert(
+      (keycode, group),
+      levels.into_iter().map(|(keysym, _)| keysym).collect(),
+    );
+    self
+  }
+
+  // keyのgroupの最初のlevelのkeysym
+  pub fn base_keysym(&self, keycode: u8, group: i32) -> XKeySymbol {
+    self
+      .keys
+      .get(&(keycode, group))
+      .and_then(|levels| levels.first().cloned())
+      .unwrap_or(0)
+  }
+
+  // 押されたkeyのKeyInput。groupはeventのstateから決める
+  pub fn key_input(&self, keycode: u8, state: u32) -> KeyInput {
+    let group = group_of(state);
+    let mut keysym = self.base_keysym(keycode, group);
+    if keysym == 0 && group != 0 {
+      // groupの少ないkeyは最初のgroupのkeysymを出す
+      keysym = self.base_keysym(keycode, 0);
+    }
+    KeyInput::new(Key::new(keysym), modifiers_of(state))
+  }
+
+  // keysymを入力できるkey。今のgroupにあるものを優先し、その中でmodifierで出せてlevelの低いものを選ぶ
+  pub fn position(&self, keysym: XKeySymbol, current_group: i32) -> Option<XKeyPosition> {
+    self
+      .positions
+      .get(&keysym)?
+      .iter()
+      .min_by_key(|(reachable, position)| {
+        (
+          position.group != current_group,
+          !reachable,
+          position.level,
+          position.keycode,
+        )
+      })
+      .map(|(_, position)| *position)
+  }
+
+  // 設定に書かれたkeyinputを、押されたkeyと同じ形にする
+  // Shiftなどで出すkeysymは、最初のlevelのkeysymとそのlevelにするmodifierに置き換える
+  pub fn normalize(&self, key_input: KeyInput, current_group: i32) -> KeyInput {
+    match self.position(key_input.key().raw_value(), current_group) {
+      Some(position) if position.modifiers != 0 => KeyInput::new(
+        Key::new(self.base_keysym(position.keycode, position.group)),
+        key_input
+          .modifiers()
+          .merge(&modifiers_of(position.modifiers)),
+      ),
+      _ => key_input,
+    }
+  }
+}
+
+// keycodeとkeysymの対応をXKBの配列から引く
+// grab、押されたkeyの解釈、keyの送信で同じ対応を使うので、cloneしたものは配列と今のgroupを共有する
+#[derive(Clone)]
+pub struct XKeyResolver {
+  display: XDisplay,
+  // 最初に引くときに配列をすべて読み、配列が変わったらrefreshで読み直させる
+  keymap: Rc<RefCell<Option<Rc<XKeymap>>>>,
+  // XkbStateNotifyで更新する。わからないときだけX serverに問い合わせる
+  group: Rc<Cell<Option<i32>>>,
+}
+
+impl XKeyResolver {
+  pub fn new(display: XDisplay) -> Self {
+    Self {
+      display,
+      keymap: Rc::new(RefCell::new(None)),
+      group: Rc::new(Cell::new(None)),
+    }
+  }
+
+  // keyboardの配列が変わったときに呼ぶ
+  pub fn refresh(&self) {
+    self.keymap.replace(None);
+    self.group.set(None);
+  }
+
+  pub fn set_group(&self, group: i32) {
+    self.group.set(Some(group));
+  }
+
+  pub fn keymap(&self) -> Rc<XKeymap> {
+    let keymap = self.keymap.borrow().clone();
+    keymap.unwrap_or_else(|| {
+      let keymap = Rc::new(self.read_keymap());
+      self.keymap.replace(Some(keymap.clone()));
+      keymap
+    })
+  }
+
+  pub fn key_input(&self, keycode: u8, state: u32) -> KeyInput {
+    self.keymap().key_input(keycode, state)
+  }
+
+  pub fn position(&self, keysym: XKeySymbol) -> Option<XKeyPosition> {
+    self.keymap().position(keysym, self.current_group())
+  }
+
+  pub fn normalize(&self, key_input: KeyInput) -> KeyInput {
+    self.keymap().normalize(key_input, self.current_group())
+  }
+
+  // すべてのkeyのgroupとlevelのkeysymを読む
+  fn read_keymap(&self) -> XKeymap {
+    let (mut min_keycode, mut max_keycode) = (0, 0);
+    unsafe {
+      xlib::XDisplayKeycodes(self.display, &mut min_keycode, &mut max_keycode);
+    }
+    let mut keymap = XKeymap::new();
+    for keycode in min_keycode..=max_keycode {
+      let keycode = keycode as u8;
+      for group in 0..MAX_GROUPS {
+        let levels: Vec<(XKeySymbol, Option<u32>)> = (0..MAX_LEVELS)
+          .map(|level| unsafe { xlib::XkbKeycodeToKeysym(self.display, keycode, group, level) })
+          .map(|keysym| (keysym, self.level_modifiers(keycode, group, keysym)))
+          .collect();
+        if levels.iter().any(|(keysym, _)| *keysym != 0) {
+          keymap = keymap.with_key(keycode, group, levels);
+        }
+      }
+    }
+    keymap
+  }
+
+  // keyをgroupで押したときにkeysymが出るmodifier。XKBのkey typeにしたがって決まる
+  fn level_modifiers(&self, keycode: u8, group: i32, keysym: XKeySymbol) -> Option<u32> {
+    if keysym == 0 {
+      return None;
+    }
+    LEVEL_MODIFIERS.iter().cloned().find(|modifiers| {
+      let (mut consumed, mut found) = (0, 0);
+      unsafe {
+        xlib::XkbLookupKeySym(
+          self.display,
+          keycode,
+          with_group(*modifiers, group),
+          &mut consumed,
+          &mut found,
+        );
+      }
+      found == keysym
+    })
+  }
+
+  // keysymを入力できるkeyがなければ0
+  pub fn keycode(&self, keysym: XKeySymbol) -> u8 {
+    self
+      .position(keysym)
+      .map(|position| position.keycode)
+      .unwrap_or(0)
+  }
+
+  pub fn current_group(&self) -> i32 {
+    if let Some(group) = self.group.get() {
+      return group;
+    }
+    let mut state: xlib::_XkbStateRec = unsafe { std::mem::zeroed() };
+    unsafe {
+      xlib::XkbGetState(self.display, XKB_USE_CORE_KBD, &mut state);
+    }
+    self.group.set(Some(state.group as i32));
+    state.group as i32
+  }
+}
+
+pub fn group_of(state: u32) -> i32 {
+  ((state & GROUP_MASK) >> GROUP_SHIFT) as i32
+}
+
+// eventのstateからmodifierを取り出す。groupはkeysymに反映しているので含めない
+pub fn modifiers_of(state: u32) -> Modifiers {
+  let state = state & !GROUP_MASK;
+  Modifiers::new(
+    (0..=31)
+      .map(|i| 1 << i)
+      .filter(|mask| state & mask > 0)
+      .map(Modifier::new)
+      .collect(),
+  )
+}
+
+// XSendEventで送るstateにgroupを入れる
+pub fn with_group(state: u32, group: i32) -> u32 {
+  (state & !GROUP_MASK) | ((group as u32) << GROUP_SHIFT)
+}
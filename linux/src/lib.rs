@@ -1,14 +1,22 @@
 pub mod config;
 
+mod config_watcher;
 mod event_source;
 mod execution_key_bind;
+mod grabber;
 mod key_handler;
+mod key_resolver;
 mod shell_command_executor;
+mod xtest_key_handler;
 
+pub use config_watcher::*;
 pub use event_source::*;
 pub use execution_key_bind::*;
+pub use grabber::*;
 pub use key_handler::*;
+pub use key_resolver::*;
 pub use shell_command_executor::*;
+pub use xtest_key_handler::*;
 
 pub type XAppIdentifier = String;
 pub type XKeySymbol = u64;
@@ -23,6 +31,7 @@ pub type KeyInput = mapper::KeyInput<XKeySymbol, XModifier>;
 pub type Key = mapper::Key<XKeySymbol>;
 pub type Modifier = mapper::Modifier<XModifier>;
 pub type Modifiers = mapper::Modifiers<XModifier>;
+pub type Grab = mapper::Grab<XKeySymbol, XModifier>;
 pub type Focus = mapper::Focus<XAppIdentifier>;
 pub type Action = mapper::Action<XKeySymbol, XModifier, XExecution>;
 pub type PossibleKeyinputFinder =
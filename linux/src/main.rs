@@ -1,38 +1,26 @@
 #[macro_use]
 extern crate log;
 
-use mapper::config::IsParser;
-
 #[macro_use]
 extern crate clap;
-use clap::App;
-use std::io::Write;
-
-use std::process::Command;
-
-fn fetch_config() -> mapper::config::Config {
-  let yaml = load_yaml!("cli.yml");
-  let matches = App::from_yaml(yaml).get_matches();
+use clap::{App, ArgMatches};
+use itertools::Itertools;
+use linux::config::ConfigFile;
+use mapper::config::IsParser;
 
+fn fetch_config_file(matches: &ArgMatches) -> ConfigFile {
   if let Some(xremap_config_fname) = matches.value_of("xremap_config") {
-    let mut converter_rb = tempfile::NamedTempFile::new().unwrap();
-    let converter_rb_source = include_str!("convert.rb");
-    converter_rb
-      .write_all(converter_rb_source.as_bytes())
-      .unwrap();
-    let output = Command::new("ruby")
-      .args(vec![
-        converter_rb.path().to_str().unwrap(),
-        xremap_config_fname,
-      ])
-      .output()
-      .unwrap();
-    return serde_json::from_reader::<&[u8], mapper::config::Config>(output.stdout.as_ref())
-      .unwrap();
+    if matches.is_present("ruby") {
+      return ConfigFile::XremapRuby(xremap_config_fname.into());
+    }
+    return ConfigFile::Xremap(xremap_config_fname.into());
   }
 
   if let Some(config_fname) = matches.value_of("config") {
-    return serde_json::from_reader(std::fs::File::open(config_fname).unwrap()).unwrap();
+    let format = matches
+      .value_of("format")
+      .and_then(mapper::config::ConfigFormat::from_name);
+    return ConfigFile::data(config_fname.into(), format);
   }
 
   panic!("xremap_config or config option must be specified!")
@@ -48,20 +36,95 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
   trace!("start");
 
-  let config = fetch_config();
-  let parser = linux::config::XParser::build(&linux::config::XIntoDomain);
-  let key_bind_for_focus = parser.build_keybind_for_focus(config.clone())?;
-  let possible_keyinput_finder = parser.build_possible_keyinput_finder(config.clone())?;
+  let yaml = load_yaml!("cli.yml");
+  let matches = App::from_yaml(yaml).get_matches();
+  if let Some(check_matches) = matches.subcommand_matches("check") {
+    check(fetch_config_file(check_matches));
+  }
+
+  let config_file = fetch_config_file(&matches);
   let display = unsafe { x11::xlib::XOpenDisplay(std::ptr::null()) };
-  let event_source = linux::XEventSource::new(display);
-  let key_handler = linux::XKeyHandler::new(display);
+  let mut event_source = linux::XEventSource::new(display).with_shutdown_signals()?;
+  // 設定を読み直すときにも使うので、終了するまで残しておく
+  let into_domain: &'static linux::config::XLayoutIntoDomain = Box::leak(Box::new(
+    linux::config::XLayoutIntoDomain::new(event_source.key_resolver()),
+  ));
+  let config = config_file.load()?;
+  let (key_bind_for_focus, possible_keyinput_finder) =
+    linux::config::build_config_for_layout(config.clone(), into_domain)?;
+  for warning in linux::config::conflict_warnings(&key_bind_for_focus) {
+    warn!("{}: {}", config_file.path().display(), warning);
+  }
+  // 監視できなくても、起動時の設定のまま動かす
+  match linux::ConfigWatcher::new(config_file.path()) {
+    Ok(config_watcher) => event_source = event_source.with_config_watcher(config_watcher),
+    Err(err) => warn!("failed to watch {:?}: {}", config_file.path(), err),
+  }
+  let key_handler: Box<dyn mapper::IsKeyHandler<linux::XKeySymbol, linux::XModifier>> =
+    match matches.value_of("injector") {
+      Some("xtest") => Box::new(linux::XTestKeyHandler::new(
+        display,
+        event_source.grabber(),
+        event_source.key_resolver(),
+      )?),
+      _ => Box::new(linux::XKeyHandler::new(
+        display,
+        event_source.key_resolver(),
+      )),
+    };
   let mut state = mapper::State::new(
     key_bind_for_focus,
     possible_keyinput_finder,
     event_source,
     key_handler,
     linux::ShellCommandExecutor,
+  )
+  .with_reloader(
+    linux::config::XReloader::new(config_file)
+      .with_layout(into_domain)
+      .with_config(config),
   );
   state.run();
+  // 終了時に離したkeyを送り切る
+  unsafe {
+    x11::xlib::XSync(display, x11::xlib::False);
+  }
   Ok(())
 }
+
+// X serverにつながずに設定を検証し、すべてのエラーを表示して終了する
+fn check(config_file: ConfigFile) -> ! {
+  let path = config_file.path().display();
+  let config = match config_file.load() {
+    Ok(config) => config,
+    Err(err) => {
+      eprintln!("{}: {}", path, err);
+      std::process::exit(1);
+    }
+  };
+  let errors = linux::config::XParser::build(&linux::config::XIntoDomain).check(config.clone());
+  for error in errors.iter() {
+    eprintln!("{}: {}", path, error);
+    let suggestions = linux::config::suggest_names(error);
+    if !suggestions.is_empty() {
+      eprintln!(
+        "  did you mean {}?",
+        suggestions
+          .iter()
+          .map(|name| format!("`{}`", name))
+          .join(", ")
+      );
+    }
+  }
+  if !errors.is_empty() {
+    std::process::exit(1);
+  }
+  // 使われないkeybindは警告だけにする
+  if let Ok((key_bind_for_focus, _)) = linux::config::build_config(config) {
+    for warning in linux::config::conflict_warnings(&key_bind_for_focus) {
+      eprintln!("{}: warning: {}", path, warning);
+    }
+  }
+  println!("{}: ok", path);
+  std::process::exit(0);
+}
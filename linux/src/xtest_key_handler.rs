@@ -0,0 +1,173 @@
+use super::*;
+use mapper::IsKeyHandler;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use x11::{xlib, xtest};
+
+// XTestで実際にkeyを押したように送る。XSendEventで送った合成eventを無視するapplicationにも届く
+pub struct XTestKeyHandler {
+  display: XDisplay,
+  grabber: Rc<XGrabber>,
+  key_resolver: XKeyResolver,
+  // 今のgroupにないkeysymを送るのに使う
+  fallback: XKeyHandler,
+}
+
+impl IsKeyHandler<XKeySymbol, XModifier> for XTestKeyHandler {
+  fn press_key(&self, key_input: KeyInput) {
+    self.key_event(key_input, true);
+  }
+
+  fn release_key(&self, key_input: KeyInput) {
+    self.key_event(key_input, false);
+  }
+}
+
+impl XTestKeyHandler {
+  // X serverがXTestに対応していなければ起動時にエラーにする
+  pub fn new(
+    display: XDisplay,
+    grabber: Rc<XGrabber>,
+    key_resolver: XKeyResolver,
+  ) -> Result<Self, Box<dyn std::error::Error>> {
+    let handler = Self {
+      display,
+      grabber,
+      fallback: XKeyHandler::new(display, key_resolver.clone()),
+      key_resolver,
+    };
+    if !handler.is_supported() {
+      return Err("X server does not support the XTest extension".into());
+    }
+    Ok(handler)
+  }
+
+  fn is_supported(&self) -> bool {
+    let (mut event_base, mut error_base, mut major, mut minor) = (0, 0, 0, 0);
+    unsafe {
+      xtest::XTestQueryExtension(
+        self.display,
+        &mut event_base,
+        &mut error_base,
+        &mut major,
+        &mut minor,
+      ) != 0
+    }
+  }
+
+  // 押されているmodifierを一時的に離したり足りないmodifierを押したりして、key_inputのとおりに届ける
+  fn key_event(&self, key_input: KeyInput, press: bool) {
+    let position = match self.key_resolver.position(key_input.key().raw_value()) {
+      Some(position) => position,
+      None => {
+        log::warn!("no keycode for {:?}", key_input.key());
+        return;
+      }
+    };
+    // XTestのkeyは今のgroupで解釈されるので、別のgroupにしかないkeysymはgroupを指定できるXSendEventで送る
+    if position.group != self.key_resolver.current_group() {
+      if press {
+        self.fallback.press_key(key_input);
+      } else {
+        self.fallback.release_key(key_input);
+      }
+      return;
+    }
+    let keycode = position.keycode;
+    // `exclam`のようにShiftなどで出すkeysymは、そのmodifierも押す
+    let modifiers = modifier_bits(&key_input.modifiers()) | position.modifiers;
+    let pressed = self.pressed_keycodes();
+    let mut released_modifiers = vec![];
+    let mut added_modifiers = vec![];
+    for (mask, keycodes) in self.modifier_keycodes() {
+      // LockやNumLockは押すと状態が切り替わってしまうので触らない
+      if config::DEFAULT_IGNORE_MODIFIERS.contains(&mask) {
+        continue;
+      }
+      let held: Vec<u8> = keycodes
+        .iter()
+        .cloned()
+        .filter(|keycode| pressed.contains(keycode))
+        .collect();
+      if modifiers & mask == 0 {
+        released_modifiers.extend(held);
+      } else if held.is_empty() {
+        added_modifiers.extend(keycodes.first().cloned());
+      }
+    }
+
+    self.grabber.set_injected(keycode, press);
+    let touched: Vec<u8> = std::iter::once(keycode)
+      .chain(released_modifiers.iter().cloned())
+      .chain(added_modifiers.iter().cloned())
+      .collect();
+    self.grabber.without_grabs(&touched, || {
+      for modifier in released_modifiers.iter() {
+        self.fake_key_event(*modifier, false);
+      }
+      for modifier in added_modifiers.iter() {
+        self.fake_key_event(*modifier, true);
+      }
+      self.fake_key_event(keycode, press);
+      for modifier in added_modifiers.iter().rev() {
+        self.fake_key_event(*modifier, false);
+      }
+      for modifier in released_modifiers.iter().rev() {
+        self.fake_key_event(*modifier, true);
+      }
+    });
+    unsafe {
+      xlib::XFlush(self.display);
+    }
+  }
+
+  fn fake_key_event(&self, keycode: u8, press: bool) {
+    unsafe {
+      xtest::XTestFakeKeyEvent(
+        self.display,
+        keycode as u32,
+        press as i32,
+        xlib::CurrentTime,
+      );
+    }
+  }
+
+  // 今押されているkeycode
+  fn pressed_keycodes(&self) -> BTreeSet<u8> {
+    let mut keys = [0 as libc::c_char; 32];
+    unsafe {
+      xlib::XQueryKeymap(self.display, keys.as_mut_ptr());
+    }
+    (0..=255u8)
+      .filter(|keycode| keys[*keycode as usize / 8] as u8 & (1 << (keycode % 8)) != 0)
+      .collect()
+  }
+
+  // ShiftからMod5までの、modifierのmaskとそれに割り当てられたkeycode
+  fn modifier_keycodes(&self) -> Vec<(u32, Vec<u8>)> {
+    unsafe {
+      let modifier_map = xlib::XGetModifierMapping(self.display);
+      if modifier_map.is_null() {
+        return vec![];
+      }
+      let per_modifier = (*modifier_map).max_keypermod as usize;
+      let keycodes = std::slice::from_raw_parts((*modifier_map).modifiermap, 8 * per_modifier);
+      let result = keycodes
+        .chunks(per_modifier.max(1))
+        .enumerate()
+        .map(|(index, keycodes)| {
+          (
+            1 << index,
+            keycodes
+              .iter()
+              .cloned()
+              .filter(|keycode| *keycode != 0)
+              .collect(),
+          )
+        })
+        .collect();
+      xlib::XFreeModifiermap(modifier_map);
+      result
+    }
+  }
+}
@@ -0,0 +1,123 @@
+use linux::config::{suggest_names, XIntoDomain};
+use mapper::config::{IsParser, Section, Side};
+use speculate::speculate;
+
+speculate! {
+  describe "XParser check" {
+    describe "#check" {
+      before {
+        let config: mapper::config::Config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C-h": { "to": "Backspace" },
+              "C-e": { "to": "End", "with": ["Shft"] },
+              "C-a": { "to": "Home" }
+            },
+            "in_app": {
+              "emacs": { "C-x": { "to": "Escpe" } }
+            }
+          }
+          "#
+        ).unwrap();
+        let errors = linux::config::XParser::build(&XIntoDomain).check(config);
+      }
+
+      it "collects every error with its section, remap and side" {
+        let located: Vec<(Option<Section>, Option<String>, Option<Side>)> = errors
+          .iter()
+          .map(|error| {
+            let location = error.location().unwrap();
+            (location.section.clone(), location.from.clone(), location.side)
+          })
+          .collect();
+        assert_eq!(
+          located,
+          vec![
+            (Some(Section::Remap), Some("C-e".to_string()), Some(Side::With)),
+            (Some(Section::Remap), Some("C-h".to_string()), Some(Side::To)),
+            (Some(Section::InApp("emacs".to_string())), Some("C-x".to_string()), Some(Side::To)),
+          ]
+        );
+      }
+
+      it "describes errors in words" {
+        assert_eq!(errors[0].to_string(), "remap `C-e` (with): unknown modifier `Shft`");
+        assert_eq!(errors[2].to_string(), "in_app[emacs] `C-x` (to): unknown key `Escpe`");
+      }
+
+      it "suggests closest key and modifier names" {
+        let suggestions: Vec<Vec<&str>> = errors.iter().map(|error| suggest_names(error)).collect();
+        assert_eq!(suggestions[0], vec!["Shift"]);
+        assert_eq!(suggestions[1][0], "BackSpace");
+        assert_eq!(suggestions[2], vec!["Escape"]);
+      }
+    }
+
+    context "when a remap has errors on several sides" {
+      before {
+        let config: mapper::config::Config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C-hh": { "to": "Lefft" },
+              "C-x C-qq": { "to": "Escape", "with": ["Shft", "Mta"] },
+              "C-j": { "sequence": ["Lft", "Home", "Rigt"] }
+            },
+            "in_app": {}
+          }
+          "#
+        ).unwrap();
+        let errors = linux::config::XParser::build(&XIntoDomain).check(config);
+      }
+
+      it "reports each of them" {
+        assert_eq!(
+          errors.iter().map(|error| error.to_string()).collect::<Vec<_>>(),
+          vec![
+            "remap `C-hh` (from): unknown key `hh`".to_string(),
+            "remap `C-hh` (to): unknown key `Lefft`".to_string(),
+            "remap `C-j` (to): unknown key `Lft`".to_string(),
+            "remap `C-j` (to): unknown key `Rigt`".to_string(),
+            "remap `C-x C-qq` (from): unknown key `qq`".to_string(),
+            "remap `C-x C-qq` (with): unknown modifier `Shft`".to_string(),
+            "remap `C-x C-qq` (with): unknown modifier `Mta`".to_string(),
+          ]
+        );
+      }
+    }
+
+    context "when ignore_modifiers has an unknown modifier" {
+      before {
+        let config: mapper::config::Config = serde_json::from_str(
+          r#"
+          {
+            "remap": {},
+            "in_app": {},
+            "ignore_modifiers": ["Mod3", "NumLck"]
+          }
+          "#
+        ).unwrap();
+        let errors = linux::config::XParser::build(&XIntoDomain).check(config);
+      }
+
+      it "reports it in the ignore_modifiers section" {
+        assert_eq!(
+          errors.iter().map(|error| error.to_string()).collect::<Vec<_>>(),
+          vec!["ignore_modifiers: unknown modifier `NumLck`".to_string()]
+        );
+        assert_eq!(suggest_names(&errors[0]), vec!["NumLock"]);
+      }
+    }
+  }
+
+  describe "suggest_names" {
+    it "prefers names with the same case and does not guess from one character" {
+      let suggest = |key: &str| {
+        suggest_names(&mapper::config::InvalidConfigError::UnexpectedKey(key.to_string()))
+      };
+      assert_eq!(suggest("hh")[0], "h");
+      assert!(suggest(" ").is_empty());
+    }
+  }
+}
@@ -0,0 +1,42 @@
+use linux::config::{build_config, conflict_warnings};
+use speculate::speculate;
+
+speculate! {
+  describe "conflict_warnings" {
+    before {
+      let config: mapper::config::Config = serde_json::from_str(
+        r#"
+        {
+          "remap": {
+            "C-Escape": { "to": "Home" },
+            "Control-Escape": { "to": "End" },
+            "C-a": { "to": "Home" },
+            "Home": { "to": "End" },
+            "Home x": { "to": "y" },
+            "Shift-Home x": { "to": "z" }
+          },
+          "in_app": {
+            "emacs": { "C-a": { "to": "C-a" } }
+          },
+          "windows": [
+            { "class_not": ["Alacritty", "/^xterm/"], "remap": { "C-a": { "to": "Home" } } }
+          ]
+        }
+        "#
+      ).unwrap();
+      let (keybind_for_focus, _) = build_config(config).unwrap();
+    }
+
+    it "describes unused keybinds with key names" {
+      assert_eq!(
+        conflict_warnings(&keybind_for_focus),
+        vec![
+          "remap: `Control-Escape` is bound more than once, only the first one is used".to_string(),
+          "remap: `Home x` never fires because `Home` also matches it".to_string(),
+          "in_app[emacs]: `Control-a` hides the global `Control-a`".to_string(),
+          "window class_not: [Alacritty, /^xterm/]: `Control-a` hides the global `Control-a`".to_string(),
+        ]
+      );
+    }
+  }
+}
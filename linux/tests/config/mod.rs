@@ -1 +1,3 @@
+mod check;
+mod conflict;
 mod x_into_domain;
@@ -0,0 +1,33 @@
+use speculate::speculate;
+
+speculate! {
+  describe "ConfigWatcher" {
+    describe "#take_changed" {
+      before {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        std::fs::write(&config_path, "{}").unwrap();
+        let watcher = linux::ConfigWatcher::new(&config_path).unwrap();
+      }
+
+      it "returns true once after config file is written" {
+        assert!(!watcher.take_changed());
+        std::fs::write(&config_path, "{ \"remap\": {} }").unwrap();
+        assert!(watcher.take_changed());
+        assert!(!watcher.take_changed());
+      }
+
+      it "returns true when config file is replaced by rename" {
+        let tmp_path = dir.path().join("config.json.swp");
+        std::fs::write(&tmp_path, "{}").unwrap();
+        std::fs::rename(&tmp_path, &config_path).unwrap();
+        assert!(watcher.take_changed());
+      }
+
+      it "ignores other files in the same directory" {
+        std::fs::write(dir.path().join("other.json"), "{}").unwrap();
+        assert!(!watcher.take_changed());
+      }
+    }
+  }
+}
@@ -0,0 +1,44 @@
+use linux::{grab_modifiers, holds_optional_modifiers, modifiers_of, Grab, Key, KeyInput};
+use speculate::speculate;
+use x11::xlib;
+
+// Control-hを、withのShiftとMod1、無視するLockとMod2でgrabする
+fn grab() -> Grab {
+  Grab::new(KeyInput::new(Key::new(0x68), modifiers_of(xlib::ControlMask)))
+    .with_optional_modifiers(modifiers_of(xlib::ShiftMask | xlib::Mod1Mask))
+    .with_ignored_modifiers(modifiers_of(xlib::LockMask | xlib::Mod2Mask))
+}
+
+speculate! {
+  describe "grab_modifiers" {
+    it "grabs only the combinations of ignored modifiers" {
+      let mut modifiers = grab_modifiers(&grab());
+      modifiers.sort();
+      let control = xlib::ControlMask;
+      assert_eq!(
+        modifiers,
+        vec![
+          control,
+          control | xlib::LockMask,
+          control | xlib::Mod2Mask,
+          control | xlib::LockMask | xlib::Mod2Mask,
+        ]
+      );
+    }
+  }
+
+  describe "holds_optional_modifiers" {
+    it "is true only while the modifiers of the key and some optional ones are held" {
+      let grab = grab();
+      assert!(holds_optional_modifiers(&grab, xlib::ControlMask | xlib::ShiftMask));
+      assert!(holds_optional_modifiers(
+        &grab,
+        xlib::ControlMask | xlib::ShiftMask | xlib::Mod1Mask | xlib::Mod2Mask
+      ));
+      // withのmodifierがなければkeyのgrabで足りる
+      assert!(!holds_optional_modifiers(&grab, xlib::ControlMask | xlib::LockMask));
+      assert!(!holds_optional_modifiers(&grab, xlib::ShiftMask));
+      assert!(!holds_optional_modifiers(&grab, xlib::ControlMask | xlib::ShiftMask | xlib::Mod4Mask));
+    }
+  }
+}
@@ -0,0 +1,90 @@
+use linux::config::keysyms;
+use linux::{with_group, Key, KeyInput, XKeyPosition, XKeySymbol, XKeymap};
+use speculate::speculate;
+use x11::xlib;
+
+fn keysym(name: &str) -> XKeySymbol {
+  *keysyms::KEYNAME_TO_KEYSYM.get(name).unwrap()
+}
+
+fn config(name: &str, modifiers: u32) -> KeyInput {
+  KeyInput::new(Key::new(keysym(name)), linux::modifiers_of(modifiers))
+}
+
+// usとruの2つのgroupに、AltGrのlevelとNumLockでしか出ないkeypadのlevelを足した配列
+fn keymap() -> XKeymap {
+  XKeymap::new()
+    .with_key(10, 0, vec![(keysym("1"), Some(0)), (keysym("exclam"), Some(xlib::ShiftMask))])
+    .with_key(10, 1, vec![(keysym("1"), Some(0)), (keysym("exclam"), Some(xlib::ShiftMask))])
+    .with_key(61, 0, vec![(keysym("slash"), Some(0)), (keysym("question"), Some(xlib::ShiftMask))])
+    .with_key(
+      24,
+      0,
+      vec![
+        (keysym("q"), Some(0)),
+        (keysym("Q"), Some(xlib::ShiftMask)),
+        (keysym("at"), Some(xlib::Mod5Mask)),
+      ],
+    )
+    .with_key(
+      24,
+      1,
+      vec![
+        (keysym("Cyrillic_shorti"), Some(0)),
+        (keysym("Cyrillic_SHORTI"), Some(xlib::ShiftMask)),
+      ],
+    )
+    .with_key(87, 0, vec![(keysym("KP_End"), Some(0)), (keysym("KP_1"), None)])
+}
+
+speculate! {
+  describe "keymap" {
+    before {
+      let keymap = keymap();
+    }
+
+    describe "#key_input" {
+      it "resolves pressed keys to the first level and keeps every modifier" {
+        assert_eq!(keymap.key_input(10, xlib::ShiftMask), config("1", xlib::ShiftMask));
+        assert_eq!(
+          keymap.key_input(24, xlib::Mod5Mask | xlib::ControlMask),
+          config("q", xlib::Mod5Mask | xlib::ControlMask)
+        );
+      }
+
+      it "reads the group from the event state and falls back to the first group" {
+        assert_eq!(keymap.key_input(24, with_group(0, 1)), config("Cyrillic_shorti", 0));
+        assert_eq!(keymap.key_input(61, with_group(xlib::ShiftMask, 1)), config("slash", xlib::ShiftMask));
+      }
+    }
+
+    describe "#normalize" {
+      it "makes keysyms typed with a level modifier equal to the pressed key" {
+        assert_eq!(keymap.normalize(config("exclam", 0), 0), keymap.key_input(10, xlib::ShiftMask));
+        assert_eq!(
+          keymap.normalize(config("question", xlib::ControlMask), 0),
+          keymap.key_input(61, xlib::ShiftMask | xlib::ControlMask)
+        );
+        assert_eq!(keymap.normalize(config("at", 0), 0), keymap.key_input(24, xlib::Mod5Mask));
+      }
+
+      it "keeps first level keysyms and the ones no modifier can reach" {
+        assert_eq!(keymap.normalize(config("slash", 0), 0), config("slash", 0));
+        assert_eq!(keymap.normalize(config("KP_1", 0), 0), config("KP_1", 0));
+      }
+    }
+
+    describe "#position" {
+      it "prefers the current group" {
+        assert_eq!(keymap.position(keysym("exclam"), 0).map(|position| position.group), Some(0));
+        assert_eq!(keymap.position(keysym("exclam"), 1).map(|position| position.group), Some(1));
+        assert_eq!(
+          keymap.position(keysym("Cyrillic_SHORTI"), 0),
+          Some(XKeyPosition { keycode: 24, group: 1, level: 1, modifiers: xlib::ShiftMask })
+        );
+        assert_eq!(keymap.position(keysym("KP_1"), 0).map(|position| position.modifiers), Some(0));
+        assert_eq!(keymap.position(keysym("a"), 0), None);
+      }
+    }
+  }
+}
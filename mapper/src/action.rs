@@ -1,4 +1,5 @@
-use super::KeyInput;
+use super::{KeyInput, Mode, Modifier};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Action<
@@ -6,8 +7,65 @@ pub enum Action<
   M: PartialOrd + Ord + std::fmt::Debug + Clone,
   C: std::fmt::Debug + Clone,
 > {
-  Key { key_input: KeyInput<K, M> },
-  Execution { execution: C },
+  Key {
+    key_input: KeyInput<K, M>,
+  },
+  Execution {
+    execution: C,
+  },
+  // sequenceの途中まで入力された。続くstrokeを待つ
+  Prefix,
+  EnterMode {
+    mode: Mode,
+  },
+  ExitMode {
+    mode: Mode,
+  },
+  ToggleMode {
+    mode: Mode,
+  },
+  // timeoutより前に離されたらtap、それ以外はholdのmodifierとして振る舞う
+  TapHold {
+    tap: KeyInput<K, M>,
+    hold: Modifier<M>,
+    timeout: Duration,
+  },
+  // chordの一部が押された。timeoutまで残りのkeyを待つ
+  Chord {
+    timeout: Duration,
+  },
+  // 優先度の低いlayerのkeybindを無効にする。keyはそのまま送られる
+  Disabled,
+  // 複数のstrokeを順番に押して離す
+  Sequence {
+    steps: Vec<Step<K, M>>,
+  },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Step<
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+> {
+  pub key_input: KeyInput<K, M>,
+  // このstrokeを送る前に待つ時間
+  pub delay: Duration,
+}
+
+impl<K: PartialEq + Eq + std::fmt::Debug + Clone, M: PartialOrd + Ord + std::fmt::Debug + Clone>
+  Step<K, M>
+{
+  pub fn new(key_input: KeyInput<K, M>) -> Self {
+    Self {
+      key_input,
+      delay: Duration::from_millis(0),
+    }
+  }
+
+  pub fn with_delay(mut self, delay: Duration) -> Self {
+    self.delay = delay;
+    self
+  }
 }
 
 impl<
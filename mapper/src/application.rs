@@ -1,12 +1,33 @@
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct Application<AppIdentifier: PartialEq + Eq + PartialOrd + Ord + Clone> {
   identifier: AppIdentifier,
+  // windowのtitle。取得できなければNone
+  title: Option<String>,
 }
 
 impl<AppIdentifier: PartialEq + Eq + PartialOrd + Ord + Clone> Application<AppIdentifier> {
   pub fn new(identifier: AppIdentifier) -> Self {
     Self {
       identifier: identifier,
+      title: None,
     }
   }
+
+  pub fn with_title(mut self, title: String) -> Self {
+    self.title = Some(title);
+    self
+  }
+
+  pub fn identifier(&self) -> &AppIdentifier {
+    &self.identifier
+  }
+
+  pub fn title(&self) -> Option<&str> {
+    self.title.as_deref()
+  }
+
+  // titleを無視して同じapplicationか
+  pub fn is_same_application(&self, other: &Self) -> bool {
+    self.identifier == other.identifier
+  }
 }
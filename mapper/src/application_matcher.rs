@@ -0,0 +1,105 @@
+use crate::*;
+
+// class_only / class_not と title_only / title_not で指定された、remapを有効にするapplicationの条件
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub struct ApplicationMatcher<A: PartialEq + Eq + PartialOrd + Ord + Clone> {
+  // Noneならすべてのapplicationが対象
+  only: Option<Vec<ApplicationPattern<A>>>,
+  not: Vec<ApplicationPattern<A>>,
+  title_only: Option<Vec<TitlePattern>>,
+  title_not: Vec<TitlePattern>,
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> ApplicationMatcher<A> {
+  pub fn new(only: Option<Vec<ApplicationPattern<A>>>, not: Vec<ApplicationPattern<A>>) -> Self {
+    Self {
+      only,
+      not,
+      title_only: None,
+      title_not: vec![],
+    }
+  }
+
+  pub fn with_titles(
+    mut self,
+    title_only: Option<Vec<TitlePattern>>,
+    title_not: Vec<TitlePattern>,
+  ) -> Self {
+    self.title_only = title_only;
+    self.title_not = title_not;
+    self
+  }
+
+  pub fn only(patterns: Vec<ApplicationPattern<A>>) -> Self {
+    Self::new(Some(patterns), vec![])
+  }
+
+  pub fn not(patterns: Vec<ApplicationPattern<A>>) -> Self {
+    Self::new(None, patterns)
+  }
+
+  // focusがないときは、class_onlyにはmatchせず、class_notにはmatchする
+  // titleが取れないときも同様に、title_onlyにはmatchせず、title_notにはmatchする
+  pub fn matches(&self, focus: &Focus<A>) -> bool {
+    match focus {
+      Focus::Focused { application } => {
+        let matches = |patterns: &Vec<ApplicationPattern<A>>| {
+          patterns.iter().any(|pattern| pattern.matches(application))
+        };
+        let title_matches = |patterns: &Vec<TitlePattern>| match application.title() {
+          Some(title) => patterns.iter().any(|pattern| pattern.matches(title)),
+          None => false,
+        };
+        !matches!(&self.only, Some(only) if !matches(only))
+          && !matches(&self.not)
+          && !matches!(&self.title_only, Some(title_only) if !title_matches(title_only))
+          && !title_matches(&self.title_not)
+      }
+      Focus::NoFocus => self.only.is_none() && self.title_only.is_none(),
+    }
+  }
+
+  // title_onlyで絞り込んでいるmatcherは、完全一致のapplicationよりも優先する
+  pub fn has_title_condition(&self) -> bool {
+    self.title_only.is_some()
+  }
+
+  // 複数のmatcherにmatchしたとき、小さいものほど優先する
+  // class_onlyが完全一致だけ < globを含む < regexを含む < class_notだけ
+  pub fn specificity(&self) -> usize {
+    match &self.only {
+      Some(only) => match only.iter().map(|pattern| pattern.kind()).max() {
+        Some(PatternKind::Exact) | None => 0,
+        Some(PatternKind::Glob) => 1,
+        Some(PatternKind::Regex) => 2,
+      },
+      None => 3,
+    }
+  }
+}
+
+// 設定と同じく "class_only: [emacs], title_not: [/^Draft/]" のように、指定された条件だけを表示する
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone + std::fmt::Display> std::fmt::Display
+  for ApplicationMatcher<A>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn list<T: std::fmt::Display>(name: &str, patterns: &[T]) -> String {
+      let patterns: Vec<String> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+      format!("{}: [{}]", name, patterns.join(", "))
+    }
+    let mut conditions = vec![];
+    if let Some(only) = &self.only {
+      conditions.push(list("class_only", only));
+    }
+    if !self.not.is_empty() {
+      conditions.push(list("class_not", &self.not));
+    }
+    if let Some(title_only) = &self.title_only {
+      conditions.push(list("title_only", title_only));
+    }
+    if !self.title_not.is_empty() {
+      conditions.push(list("title_not", &self.title_not));
+    }
+    write!(f, "{}", conditions.join(", "))
+  }
+}
@@ -0,0 +1,259 @@
+use crate::*;
+use std::rc::Rc;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum PatternKind {
+  Exact,
+  Glob,
+  Regex,
+}
+
+// applicationの指定。完全一致のほか、globやregexで複数のapplicationにmatchさせられる
+#[derive(Clone)]
+pub enum ApplicationPattern<A: PartialEq + Eq + PartialOrd + Ord + Clone> {
+  Exact(Application<A>),
+  Matching {
+    kind: PatternKind,
+    // 比較や表示にはpatternの文字列を使う
+    source: String,
+    matches: Rc<dyn Fn(&A) -> bool>,
+  },
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> ApplicationPattern<A> {
+  pub fn exact(application: Application<A>) -> Self {
+    ApplicationPattern::Exact(application)
+  }
+
+  pub fn kind(&self) -> PatternKind {
+    match self {
+      ApplicationPattern::Exact(_) => PatternKind::Exact,
+      ApplicationPattern::Matching { kind, .. } => *kind,
+    }
+  }
+
+  pub fn matches(&self, application: &Application<A>) -> bool {
+    match self {
+      ApplicationPattern::Exact(exact) => exact.is_same_application(application),
+      ApplicationPattern::Matching { matches, .. } => matches(application.identifier()),
+    }
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone + AsRef<str> + From<String> + 'static>
+  ApplicationPattern<A>
+{
+  // "/.../" はregex、*?[ を含むものはglob、それ以外は完全一致として扱う
+  pub fn parse(source: String) -> Result<Self, regex::Error> {
+    if is_regex(&source) {
+      Self::regex(&source[1..source.len() - 1])
+    } else if source.contains(&['*', '?', '['][..]) {
+      Self::glob(&source)
+    } else {
+      Ok(Self::exact(Application::new(A::from(source))))
+    }
+  }
+
+  pub fn regex(source: &str) -> Result<Self, regex::Error> {
+    Ok(Self::matching(
+      PatternKind::Regex,
+      source,
+      regex::Regex::new(source)?,
+    ))
+  }
+
+  pub fn glob(source: &str) -> Result<Self, regex::Error> {
+    Ok(Self::matching(
+      PatternKind::Glob,
+      source,
+      regex::Regex::new(&glob_to_regex(source))?,
+    ))
+  }
+
+  fn matching(kind: PatternKind, source: &str, regex: regex::Regex) -> Self {
+    ApplicationPattern::Matching {
+      kind,
+      source: source.to_string(),
+      matches: Rc::new(move |identifier: &A| regex.is_match(identifier.as_ref())),
+    }
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> ApplicationPattern<A> {
+  fn sort_key(&self) -> (PatternKind, Option<&Application<A>>, Option<&str>) {
+    match self {
+      ApplicationPattern::Exact(application) => (PatternKind::Exact, Some(application), None),
+      ApplicationPattern::Matching { kind, source, .. } => (*kind, None, Some(source)),
+    }
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> PartialEq for ApplicationPattern<A> {
+  fn eq(&self, other: &Self) -> bool {
+    self.sort_key() == other.sort_key()
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> Eq for ApplicationPattern<A> {}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> PartialOrd for ApplicationPattern<A> {
+  fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> Ord for ApplicationPattern<A> {
+  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    self.sort_key().cmp(&other.sort_key())
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone + std::fmt::Debug> std::fmt::Debug
+  for ApplicationPattern<A>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ApplicationPattern::Exact(application) => write!(f, "Exact({:?})", application),
+      ApplicationPattern::Matching { kind, source, .. } => write!(f, "{:?}({:?})", kind, source),
+    }
+  }
+}
+
+// 設定に書かれたとおりに表示する。regexは/で囲む
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone + std::fmt::Display> std::fmt::Display
+  for ApplicationPattern<A>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ApplicationPattern::Exact(application) => write!(f, "{}", application.identifier()),
+      ApplicationPattern::Matching {
+        kind: PatternKind::Regex,
+        source,
+        ..
+      } => write!(f, "/{}/", source),
+      ApplicationPattern::Matching { source, .. } => write!(f, "{}", source),
+    }
+  }
+}
+
+// windowのtitleの指定。applicationと同じく完全一致、glob、regexで指定する
+#[derive(Clone)]
+pub struct TitlePattern {
+  kind: PatternKind,
+  // 比較や表示にはpatternの文字列を使う
+  source: String,
+  matches: Rc<dyn Fn(&str) -> bool>,
+}
+
+impl TitlePattern {
+  pub fn parse(source: String) -> Result<Self, regex::Error> {
+    let (kind, regex) = if is_regex(&source) {
+      (
+        PatternKind::Regex,
+        regex::Regex::new(&source[1..source.len() - 1])?,
+      )
+    } else if is_glob(&source) {
+      (
+        PatternKind::Glob,
+        regex::Regex::new(&glob_to_regex(&source))?,
+      )
+    } else {
+      (
+        PatternKind::Exact,
+        regex::Regex::new(&format!("^{}$", regex::escape(&source)))?,
+      )
+    };
+    Ok(Self {
+      kind,
+      source,
+      matches: Rc::new(move |title: &str| regex.is_match(title)),
+    })
+  }
+
+  pub fn kind(&self) -> PatternKind {
+    self.kind
+  }
+
+  pub fn matches(&self, title: &str) -> bool {
+    (self.matches)(title)
+  }
+}
+
+impl std::fmt::Debug for TitlePattern {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?}({:?})", self.kind, self.source)
+  }
+}
+
+impl std::fmt::Display for TitlePattern {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.source)
+  }
+}
+
+impl PartialEq for TitlePattern {
+  fn eq(&self, other: &Self) -> bool {
+    (self.kind, &self.source) == (other.kind, &other.source)
+  }
+}
+
+impl Eq for TitlePattern {}
+
+impl PartialOrd for TitlePattern {
+  fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for TitlePattern {
+  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    (self.kind, &self.source).cmp(&(other.kind, &other.source))
+  }
+}
+
+fn is_regex(source: &str) -> bool {
+  source.len() > 1 && source.starts_with('/') && source.ends_with('/')
+}
+
+fn is_glob(source: &str) -> bool {
+  source.contains(&['*', '?', '['][..])
+}
+
+// *は任意の文字列、?は任意の1文字、[...]は文字クラス。全体にmatchしたときだけtrueになるregexにする
+// 文字クラスの中は a-z のような範囲を保つため、そのままregexに写す
+fn glob_to_regex(source: &str) -> String {
+  let mut pattern = String::from("^");
+  // 文字クラスの中なら、クラスの中身が始まるpatternの位置
+  let mut class_start: Option<usize> = None;
+  for c in source.chars() {
+    let at_class_start = class_start == Some(pattern.len());
+    match (c, class_start) {
+      ('*', None) => pattern.push_str(".*"),
+      ('?', None) => pattern.push('.'),
+      ('[', None) => {
+        pattern.push('[');
+        class_start = Some(pattern.len());
+      }
+      (_, None) => pattern.push_str(&regex::escape(&c.to_string())),
+      // 先頭の!と^は否定
+      ('!', Some(_)) | ('^', Some(_)) if at_class_start && pattern.ends_with('[') => {
+        pattern.push('^');
+        class_start = Some(pattern.len());
+      }
+      // 先頭の]はクラスを閉じずに文字として扱う
+      (']', Some(_)) if at_class_start => pattern.push_str("\\]"),
+      (']', Some(_)) => {
+        pattern.push(']');
+        class_start = None;
+      }
+      // regexでは入れ子のクラスや&&、~~の演算になってしまう文字
+      ('\\', Some(_)) | ('[', Some(_)) | ('&', Some(_)) | ('~', Some(_)) => {
+        pattern.push('\\');
+        pattern.push(c);
+      }
+      (_, Some(_)) => pattern.push(c),
+    }
+  }
+  pattern.push('$');
+  pattern
+}
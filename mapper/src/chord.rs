@@ -0,0 +1,26 @@
+use crate::*;
+use std::time::Instant;
+
+// chordの残りのkeyを待っている状態。押されたkeyは成立するまで送らずに貯めておく
+pub struct PendingChord<
+  K: PartialEq + Eq + Clone + std::fmt::Debug,
+  M: PartialOrd + Ord + Clone + std::fmt::Debug,
+> {
+  pub strokes: Vec<KeyInput<K, M>>,
+  pub deadline: Instant,
+}
+
+impl<K: PartialEq + Eq + Clone + std::fmt::Debug, M: PartialOrd + Ord + Clone + std::fmt::Debug>
+  PendingChord<K, M>
+{
+  pub fn new(key_input: KeyInput<K, M>, deadline: Instant) -> Self {
+    Self {
+      strokes: vec![key_input],
+      deadline,
+    }
+  }
+
+  pub fn contains_key(&self, key: &Key<K>) -> bool {
+    self.strokes.iter().any(|stroke| stroke.key() == *key)
+  }
+}
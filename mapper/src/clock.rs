@@ -0,0 +1,18 @@
+use std::time::{Duration, Instant};
+
+pub trait IsClock {
+  fn now(&self) -> Instant;
+  fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl IsClock for SystemClock {
+  fn now(&self) -> Instant {
+    Instant::now()
+  }
+
+  fn sleep(&self, duration: Duration) {
+    std::thread::sleep(duration);
+  }
+}
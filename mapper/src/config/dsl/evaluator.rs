@@ -0,0 +1,564 @@
+use super::*;
+use serde_json::{json, Map};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Nil,
+  Bool(bool),
+  Int(i64),
+  Str(String),
+  Regex(String),
+  Array(Vec<Value>),
+  Range(i64, i64),
+  // execute などが返すaction
+  Action(serde_json::Value),
+}
+
+impl Value {
+  fn type_name(&self) -> &'static str {
+    match self {
+      Value::Nil => "nil",
+      Value::Bool(_) => "boolean",
+      Value::Int(_) => "integer",
+      Value::Str(_) => "string",
+      Value::Regex(_) => "regexp",
+      Value::Array(_) => "array",
+      Value::Range(_, _) => "range",
+      Value::Action(_) => "action",
+    }
+  }
+
+  fn to_json(&self) -> serde_json::Value {
+    match self {
+      Value::Nil => serde_json::Value::Null,
+      Value::Bool(value) => json!(value),
+      Value::Int(value) => json!(value),
+      Value::Str(value) => json!(value),
+      Value::Regex(source) => json!(format!("/{}/", source)),
+      Value::Array(values) => serde_json::Value::Array(values.iter().map(Value::to_json).collect()),
+      Value::Range(start, end) => json!((*start..=*end).collect::<Vec<_>>()),
+      Value::Action(action) => action.clone(),
+    }
+  }
+
+  fn to_display(&self) -> String {
+    match self {
+      Value::Nil => String::new(),
+      Value::Bool(value) => value.to_string(),
+      Value::Int(value) => value.to_string(),
+      Value::Str(value) => value.clone(),
+      Value::Regex(source) => format!("/{}/", source),
+      Value::Array(_) | Value::Range(_, _) | Value::Action(_) => self.to_json().to_string(),
+    }
+  }
+}
+
+// remapを登録する先
+#[derive(Clone)]
+enum Context {
+  Global,
+  InApp(String),
+  Mode(String),
+  Window(usize),
+}
+
+impl Context {
+  fn section(&self) -> super::super::Section {
+    match self {
+      Context::Global => super::super::Section::Remap,
+      Context::InApp(class) => super::super::Section::InApp(class.clone()),
+      Context::Mode(mode) => super::super::Section::Mode(mode.clone()),
+      Context::Window(index) => super::super::Section::Window(*index),
+    }
+  }
+}
+
+// convert.rbと同じ形のJSONを組み立ててからConfigにする
+pub struct Evaluator {
+  scopes: Vec<BTreeMap<String, Value>>,
+  global: Map<String, serde_json::Value>,
+  in_app: Map<String, serde_json::Value>,
+  modes: Map<String, serde_json::Value>,
+  windows: Vec<serde_json::Value>,
+  chord_timeout: Option<i64>,
+  ignore_modifiers: Vec<String>,
+  priority: serde_json::Value,
+  context: Context,
+  positions: super::super::Positions,
+}
+
+impl Default for Evaluator {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Evaluator {
+  pub fn new() -> Self {
+    Self {
+      scopes: vec![BTreeMap::new()],
+      global: Map::new(),
+      in_app: Map::new(),
+      modes: Map::new(),
+      windows: vec![],
+      chord_timeout: None,
+      ignore_modifiers: vec![],
+      priority: json!({ "in_app": {}, "modes": {} }),
+      context: Context::Global,
+      positions: BTreeMap::new(),
+    }
+  }
+
+  pub fn evaluate(mut self, program: &[Node]) -> Result<super::super::Config, DslError> {
+    for node in program {
+      self.eval(node)?;
+    }
+    let config = json!({
+      "remap": self.global,
+      "in_app": self.in_app,
+      "modes": self.modes,
+      "chord_timeout": self.chord_timeout,
+      "ignore_modifiers": self.ignore_modifiers,
+      "priority": self.priority,
+      "windows": self.windows,
+    });
+    let mut config: super::super::Config = serde_json::from_value(config)
+      .map_err(|err| DslError::new(Pos { line: 1, column: 1 }, err.to_string()))?;
+    config.positions = self.positions;
+    Ok(config)
+  }
+
+  fn eval(&mut self, node: &Node) -> Result<Value, DslError> {
+    match &node.expr {
+      Expr::Nil => Ok(Value::Nil),
+      Expr::Bool(value) => Ok(Value::Bool(*value)),
+      Expr::Int(value) => Ok(Value::Int(*value)),
+      Expr::Regex(source) => Ok(Value::Regex(source.clone())),
+      Expr::Str(segments) => {
+        let mut string = String::new();
+        for segment in segments {
+          match segment {
+            StrSegment::Literal(literal) => string.push_str(literal),
+            StrSegment::Interpolation(node) => string.push_str(&self.eval(node)?.to_display()),
+          }
+        }
+        Ok(Value::Str(string))
+      }
+      Expr::Array(elements) => Ok(Value::Array(
+        elements
+          .iter()
+          .map(|element| self.eval(element))
+          .collect::<Result<_, _>>()?,
+      )),
+      Expr::Range(start, end) => match (self.eval(start)?, self.eval(end)?) {
+        (Value::Int(start), Value::Int(end)) => Ok(Value::Range(start, end)),
+        (start, end) => Err(DslError::new(
+          node.pos,
+          format!(
+            "range of {} and {} is not supported",
+            start.type_name(),
+            end.type_name()
+          ),
+        )),
+      },
+      Expr::Var(name) => self
+        .scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.get(name))
+        .cloned()
+        .ok_or_else(|| DslError::new(node.pos, format!("undefined local variable `{}`", name))),
+      Expr::Assign(name, value) => {
+        let value = self.eval(value)?;
+        // 外側のscopeにあればそれを書き換える
+        match self
+          .scopes
+          .iter_mut()
+          .rev()
+          .find(|scope| scope.contains_key(name))
+        {
+          Some(scope) => scope.insert(name.clone(), value.clone()),
+          None => self
+            .scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.clone(), value.clone()),
+        };
+        Ok(value)
+      }
+      Expr::Call(call) => match &call.receiver {
+        Some(receiver) => {
+          let receiver = self.eval(receiver)?;
+          self.call_method(receiver, call, node.pos)
+        }
+        None => self.call_function(call, node.pos),
+      },
+    }
+  }
+
+  fn call_method(&mut self, receiver: Value, call: &Call, pos: Pos) -> Result<Value, DslError> {
+    let args = Args::new(self, call, pos)?;
+    match (call.name.as_str(), &receiver) {
+      ("each", Value::Array(_)) | ("each", Value::Range(_, _)) => {
+        args.expect(0, &[])?;
+        let block = args.block()?;
+        let elements = match &receiver {
+          Value::Array(elements) => elements.clone(),
+          Value::Range(start, end) => (*start..=*end).map(Value::Int).collect(),
+          _ => unreachable!(),
+        };
+        for element in elements {
+          // 配列の要素は複数のblock引数に展開する
+          let values = match (element, block.params.len()) {
+            (Value::Array(values), len) if len > 1 => values,
+            (element, _) => vec![element],
+          };
+          self.yield_block(block, values)?;
+        }
+        Ok(receiver)
+      }
+      ("to_s", _) => {
+        args.expect(0, &[])?;
+        Ok(Value::Str(receiver.to_display()))
+      }
+      ("upcase", Value::Str(string)) => {
+        args.expect(0, &[])?;
+        Ok(Value::Str(string.to_uppercase()))
+      }
+      ("downcase", Value::Str(string)) => {
+        args.expect(0, &[])?;
+        Ok(Value::Str(string.to_lowercase()))
+      }
+      (name, receiver) => Err(DslError::new(
+        pos,
+        format!("undefined method `{}` for {}", name, receiver.type_name()),
+      )),
+    }
+  }
+
+  fn call_function(&mut self, call: &Call, pos: Pos) -> Result<Value, DslError> {
+    let args = Args::new(self, call, pos)?;
+    match call.name.as_str() {
+      "remap" => {
+        args.expect(1, &["to", "with_modifier"])?;
+        let key = args.string(0)?;
+        let action = match (args.keyword("to")?, args.keyword("with_modifier")?) {
+          (Value::Str(to), Value::Nil) => json!({ "to": to, "with": [] }),
+          (Value::Str(to), with) => {
+            json!({ "to": to, "with": args.strings(with, "with_modifier")? })
+          }
+          (Value::Array(sequence), Value::Nil) => {
+            json!({ "sequence": Value::Array(sequence).to_json() })
+          }
+          (Value::Action(action), Value::Nil) => action,
+          (Value::Nil, _) => return Err(DslError::new(pos, "missing keyword: to")),
+          (to, Value::Nil) => {
+            return Err(DslError::new(
+              pos,
+              format!("unexpected {} for to:", to.type_name()),
+            ))
+          }
+          (_, _) => return Err(DslError::new(pos, "with_modifier needs a key to: remap to")),
+        };
+        self.positions.insert(
+          (self.context.section(), super::super::KeyInput(key.clone())),
+          pos,
+        );
+        self.remaps().insert(key, action);
+        Ok(Value::Nil)
+      }
+      "execute" => {
+        args.expect(1, &[])?;
+        Ok(Value::Action(json!({ "execute": args.string(0)? })))
+      }
+      name @ "enter_mode" | name @ "exit_mode" | name @ "toggle_mode" => {
+        args.expect(1, &[])?;
+        let mut action = Map::new();
+        action.insert(name.to_string(), json!(args.string(0)?));
+        Ok(Value::Action(serde_json::Value::Object(action)))
+      }
+      "tap_hold" => {
+        args.expect(0, &["tap", "hold", "timeout"])?;
+        let mut action = json!({
+          "tap": args.required_string("tap")?,
+          "hold": args.required_string("hold")?,
+        });
+        if let Value::Int(timeout) = args.keyword("timeout")? {
+          action["hold_timeout"] = json!(timeout);
+        }
+        Ok(Value::Action(action))
+      }
+      "disable" => {
+        args.expect(0, &[])?;
+        Ok(Value::Action(json!({ "disable": true })))
+      }
+      "delay" => {
+        args.expect(2, &[])?;
+        Ok(Value::Action(
+          json!({ "to": args.string(0)?, "delay": args.int(1)? }),
+        ))
+      }
+      "chord_timeout" => {
+        args.expect(1, &[])?;
+        self.chord_timeout = Some(args.int(0)?);
+        Ok(Value::Nil)
+      }
+      "ignore_modifiers" => {
+        args.expect(1, &[])?;
+        let modifiers = args.strings(args.args[0].clone(), "ignore_modifiers")?;
+        self.ignore_modifiers.extend(modifiers);
+        Ok(Value::Nil)
+      }
+      "global_priority" => {
+        args.expect(1, &[])?;
+        self.priority["remap"] = json!(args.int(0)?);
+        Ok(Value::Nil)
+      }
+      "window" => {
+        args.expect(
+          0,
+          &[
+            "class_only",
+            "class_not",
+            "title_only",
+            "title_not",
+            "priority",
+          ],
+        )?;
+        let block = args.block()?;
+        let patterns = |name| -> Result<Option<Vec<String>>, DslError> {
+          match args.keyword(name)? {
+            Value::Nil => Ok(None),
+            value => args.patterns(value, name).map(Some),
+          }
+        };
+        let class_only = patterns("class_only")?;
+        let class_not = patterns("class_not")?;
+        let title_only = patterns("title_only")?;
+        let title_not = patterns("title_not")?;
+        let priority = args.optional_int("priority")?;
+
+        if class_not.is_some() || title_only.is_some() || title_not.is_some() {
+          let mut window = json!({
+            "class_only": class_only,
+            "class_not": class_not.unwrap_or_default(),
+            "title_only": title_only,
+            "title_not": title_not.unwrap_or_default(),
+            "remap": {},
+          });
+          if let Some(priority) = priority {
+            window["priority"] = json!(priority);
+          }
+          self.windows.push(window);
+          let context = Context::Window(self.windows.len() - 1);
+          self.with_context(context, block)?;
+        } else {
+          for class in class_only.unwrap_or_default() {
+            if let Some(priority) = priority {
+              self.priority["in_app"][&class] = json!(priority);
+            }
+            self.with_context(Context::InApp(class), block)?;
+          }
+        }
+        Ok(Value::Nil)
+      }
+      "mode" => {
+        args.expect(1, &["priority"])?;
+        let block = args.block()?;
+        let name = args.string(0)?;
+        if let Some(priority) = args.optional_int("priority")? {
+          self.priority["modes"][&name] = json!(priority);
+        }
+        self.with_context(Context::Mode(name), block)?;
+        Ok(Value::Nil)
+      }
+      name => Err(DslError::new(pos, format!("undefined method `{}`", name))),
+    }
+  }
+
+  fn with_context(&mut self, context: Context, block: &Block) -> Result<(), DslError> {
+    let previous = std::mem::replace(&mut self.context, context);
+    let result = self.yield_block(block, vec![]);
+    self.context = previous;
+    result.map(|_| ())
+  }
+
+  fn yield_block(&mut self, block: &Block, values: Vec<Value>) -> Result<Value, DslError> {
+    let mut scope = BTreeMap::new();
+    let mut values = values.into_iter();
+    for param in &block.params {
+      scope.insert(param.clone(), values.next().unwrap_or(Value::Nil));
+    }
+    self.scopes.push(scope);
+    let mut last = Ok(Value::Nil);
+    for node in &block.body {
+      last = self.eval(node);
+      if last.is_err() {
+        break;
+      }
+    }
+    self.scopes.pop();
+    last
+  }
+
+  fn remaps(&mut self) -> &mut Map<String, serde_json::Value> {
+    let remaps = match &self.context {
+      Context::Global => return &mut self.global,
+      Context::InApp(class) => self.in_app.entry(class.clone()),
+      Context::Mode(mode) => self.modes.entry(mode.clone()),
+      Context::Window(index) => return self.windows[*index]["remap"].as_object_mut().unwrap(),
+    };
+    remaps.or_insert_with(|| json!({})).as_object_mut().unwrap()
+  }
+}
+
+// 評価済みの引数
+struct Args<'a> {
+  call: &'a Call,
+  pos: Pos,
+  args: Vec<Value>,
+  kwargs: BTreeMap<String, Value>,
+}
+
+impl<'a> Args<'a> {
+  fn new(evaluator: &mut Evaluator, call: &'a Call, pos: Pos) -> Result<Self, DslError> {
+    let args = call
+      .args
+      .iter()
+      .map(|arg| evaluator.eval(arg))
+      .collect::<Result<_, _>>()?;
+    let mut kwargs = BTreeMap::new();
+    for (name, arg) in &call.kwargs {
+      kwargs.insert(name.clone(), evaluator.eval(arg)?);
+    }
+    Ok(Self {
+      call,
+      pos,
+      args,
+      kwargs,
+    })
+  }
+
+  fn error<T>(&self, message: String) -> Result<T, DslError> {
+    Err(DslError::new(self.pos, message))
+  }
+
+  fn expect(&self, count: usize, keywords: &[&str]) -> Result<(), DslError> {
+    if self.args.len() != count {
+      return self.error(format!(
+        "wrong number of arguments for `{}` (given {}, expected {})",
+        self.call.name,
+        self.args.len(),
+        count
+      ));
+    }
+    match self
+      .kwargs
+      .keys()
+      .find(|name| !keywords.contains(&name.as_str()))
+    {
+      Some(name) => self.error(format!(
+        "unknown keyword for `{}`: {}",
+        self.call.name, name
+      )),
+      None => Ok(()),
+    }
+  }
+
+  fn block(&self) -> Result<&'a Block, DslError> {
+    match &self.call.block {
+      Some(block) => Ok(block),
+      None => self.error(format!("`{}` needs a block", self.call.name)),
+    }
+  }
+
+  fn string(&self, index: usize) -> Result<String, DslError> {
+    match &self.args[index] {
+      Value::Str(string) => Ok(string.clone()),
+      value => self.error(format!(
+        "expected string for `{}`, found {}",
+        self.call.name,
+        value.type_name()
+      )),
+    }
+  }
+
+  fn int(&self, index: usize) -> Result<i64, DslError> {
+    match &self.args[index] {
+      Value::Int(value) => Ok(*value),
+      value => self.error(format!(
+        "expected integer for `{}`, found {}",
+        self.call.name,
+        value.type_name()
+      )),
+    }
+  }
+
+  fn keyword(&self, name: &str) -> Result<Value, DslError> {
+    Ok(self.kwargs.get(name).cloned().unwrap_or(Value::Nil))
+  }
+
+  fn required_string(&self, name: &str) -> Result<String, DslError> {
+    match self.keyword(name)? {
+      Value::Str(string) => Ok(string),
+      Value::Nil => self.error(format!("missing keyword: {}", name)),
+      value => self.error(format!(
+        "expected string for {}:, found {}",
+        name,
+        value.type_name()
+      )),
+    }
+  }
+
+  fn optional_int(&self, name: &str) -> Result<Option<i64>, DslError> {
+    match self.keyword(name)? {
+      Value::Int(value) => Ok(Some(value)),
+      Value::Nil => Ok(None),
+      value => self.error(format!(
+        "expected integer for {}:, found {}",
+        name,
+        value.type_name()
+      )),
+    }
+  }
+
+  // stringか、stringの配列
+  fn strings(&self, value: Value, name: &str) -> Result<Vec<String>, DslError> {
+    let values = match value {
+      Value::Array(values) => values,
+      value => vec![value],
+    };
+    values
+      .into_iter()
+      .map(|value| match value {
+        Value::Str(string) => Ok(string),
+        value => self.error(format!(
+          "expected string for {}:, found {}",
+          name,
+          value.type_name()
+        )),
+      })
+      .collect()
+  }
+
+  // regexpは `/.../` の文字列にする
+  fn patterns(&self, value: Value, name: &str) -> Result<Vec<String>, DslError> {
+    let values = match value {
+      Value::Array(values) => values,
+      value => vec![value],
+    };
+    self.strings(
+      Value::Array(
+        values
+          .into_iter()
+          .map(|value| match value {
+            Value::Regex(source) => Value::Str(format!("/{}/", source)),
+            value => value,
+          })
+          .collect(),
+      ),
+      name,
+    )
+  }
+}
@@ -0,0 +1,349 @@
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+  Ident(String),
+  // `to:` のようなkeyword引数の名前
+  Label(String),
+  Str(Vec<StrPart>),
+  Int(i64),
+  Regex(String),
+  // %w[...]
+  Words(Vec<String>),
+  Comma,
+  Dot,
+  DotDot,
+  LParen,
+  RParen,
+  LBracket,
+  RBracket,
+  LBrace,
+  RBrace,
+  Pipe,
+  Assign,
+  Newline,
+  Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrPart {
+  Literal(String),
+  // "#{...}" の中身
+  Interpolation(Vec<Spanned>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned {
+  pub token: Token,
+  pub pos: Pos,
+  // 直前に空白があるか。`f (x)` と `f(x)` を区別するのに使う
+  pub space_before: bool,
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Spanned>, DslError> {
+  let mut lexer = Lexer {
+    chars: source.chars().collect(),
+    index: 0,
+    pos: Pos { line: 1, column: 1 },
+  };
+  let mut tokens = vec![];
+  loop {
+    let spanned = lexer.next_token()?;
+    let eof = spanned.token == Token::Eof;
+    tokens.push(spanned);
+    if eof {
+      return Ok(tokens);
+    }
+  }
+}
+
+struct Lexer {
+  chars: Vec<char>,
+  index: usize,
+  pos: Pos,
+}
+
+impl Lexer {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.index).cloned()
+  }
+
+  fn peek_at(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.index + offset).cloned()
+  }
+
+  fn bump(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.index += 1;
+    if c == '\n' {
+      self.pos.line += 1;
+      self.pos.column = 1;
+    } else {
+      self.pos.column += 1;
+    }
+    Some(c)
+  }
+
+  fn error<T>(&self, pos: Pos, message: impl Into<String>) -> Result<T, DslError> {
+    Err(DslError::new(pos, message))
+  }
+
+  // 空白とコメントを読み飛ばす。改行は読み飛ばさない
+  fn skip_space(&mut self) -> bool {
+    let mut skipped = false;
+    loop {
+      match self.peek() {
+        Some(' ') | Some('\t') | Some('\r') => {
+          self.bump();
+        }
+        Some('\\') if self.peek_at(1) == Some('\n') => {
+          self.bump();
+          self.bump();
+        }
+        Some('#') => {
+          while !matches!(self.peek(), Some('\n') | None) {
+            self.bump();
+          }
+        }
+        _ => return skipped,
+      }
+      skipped = true;
+    }
+  }
+
+  fn next_token(&mut self) -> Result<Spanned, DslError> {
+    let space_before = self.skip_space();
+    let pos = self.pos;
+    let token = match self.peek() {
+      None => Token::Eof,
+      Some(c) => match c {
+        '\n' | ';' => {
+          self.bump();
+          Token::Newline
+        }
+        ',' => self.single(Token::Comma),
+        '(' => self.single(Token::LParen),
+        ')' => self.single(Token::RParen),
+        '[' => self.single(Token::LBracket),
+        ']' => self.single(Token::RBracket),
+        '{' => self.single(Token::LBrace),
+        '}' => self.single(Token::RBrace),
+        '|' => self.single(Token::Pipe),
+        '.' if self.peek_at(1) == Some('.') => {
+          self.bump();
+          self.single(Token::DotDot)
+        }
+        '.' => self.single(Token::Dot),
+        '=' if !matches!(self.peek_at(1), Some('=') | Some('>') | Some('~')) => {
+          self.single(Token::Assign)
+        }
+        '\'' => self.single_quoted()?,
+        '"' => self.double_quoted()?,
+        '/' => self.regex()?,
+        '%' if self.peek_at(1) == Some('w') => self.words()?,
+        ':' if matches!(self.peek_at(1), Some(c) if is_ident_start(c)) => {
+          self.bump();
+          Token::Str(vec![StrPart::Literal(self.ident_chars())])
+        }
+        '-' if matches!(self.peek_at(1), Some(c) if c.is_ascii_digit()) => {
+          self.bump();
+          Token::Int(-self.integer(pos)?)
+        }
+        c if c.is_ascii_digit() => Token::Int(self.integer(pos)?),
+        c if is_ident_start(c) => {
+          let name = self.ident_chars();
+          if self.peek() == Some(':') && self.peek_at(1) != Some(':') {
+            self.bump();
+            Token::Label(name)
+          } else {
+            Token::Ident(name)
+          }
+        }
+        c => return self.error(pos, format!("unexpected character `{}`", c)),
+      },
+    };
+    Ok(Spanned {
+      token,
+      pos,
+      space_before,
+    })
+  }
+
+  fn single(&mut self, token: Token) -> Token {
+    self.bump();
+    token
+  }
+
+  fn ident_chars(&mut self) -> String {
+    let mut name = String::new();
+    while let Some(c) = self.peek() {
+      if c.is_ascii_alphanumeric() || c == '_' {
+        name.push(c);
+        self.bump();
+      } else {
+        break;
+      }
+    }
+    if let Some(c @ '?') | Some(c @ '!') = self.peek() {
+      name.push(c);
+      self.bump();
+    }
+    name
+  }
+
+  fn integer(&mut self, pos: Pos) -> Result<i64, DslError> {
+    let mut digits = String::new();
+    while let Some(c) = self.peek() {
+      if c.is_ascii_digit() {
+        digits.push(c);
+      } else if c != '_' {
+        break;
+      }
+      self.bump();
+    }
+    match digits.parse() {
+      Ok(value) => Ok(value),
+      Err(_) => self.error(pos, format!("too large integer `{}`", digits)),
+    }
+  }
+
+  // '...' は \\ と \' だけをescapeする
+  fn single_quoted(&mut self) -> Result<Token, DslError> {
+    let pos = self.pos;
+    self.bump();
+    let mut literal = String::new();
+    loop {
+      match self.bump() {
+        None => return self.error(pos, "unterminated string"),
+        Some('\'') => return Ok(Token::Str(vec![StrPart::Literal(literal)])),
+        Some('\\') if matches!(self.peek(), Some('\\') | Some('\'')) => {
+          literal.push(self.bump().unwrap())
+        }
+        Some(c) => literal.push(c),
+      }
+    }
+  }
+
+  fn double_quoted(&mut self) -> Result<Token, DslError> {
+    let pos = self.pos;
+    self.bump();
+    let mut parts = vec![];
+    let mut literal = String::new();
+    loop {
+      match self.bump() {
+        None => return self.error(pos, "unterminated string"),
+        Some('"') => break,
+        Some('\\') => match self.bump() {
+          Some('n') => literal.push('\n'),
+          Some('t') => literal.push('\t'),
+          Some('e') => literal.push('\u{1b}'),
+          Some('0') => literal.push('\0'),
+          Some(c) => literal.push(c),
+          None => return self.error(pos, "unterminated string"),
+        },
+        Some('#') if self.peek() == Some('{') => {
+          self.bump();
+          if !literal.is_empty() {
+            parts.push(StrPart::Literal(std::mem::take(&mut literal)));
+          }
+          parts.push(StrPart::Interpolation(self.interpolation()?));
+        }
+        Some(c) => literal.push(c),
+      }
+    }
+    if !literal.is_empty() || parts.is_empty() {
+      parts.push(StrPart::Literal(literal));
+    }
+    Ok(Token::Str(parts))
+  }
+
+  // 対応する } までのtoken列
+  fn interpolation(&mut self) -> Result<Vec<Spanned>, DslError> {
+    let mut tokens = vec![];
+    let mut depth = 0;
+    loop {
+      let spanned = self.next_token()?;
+      match spanned.token {
+        Token::Eof => return self.error(spanned.pos, "unterminated interpolation"),
+        Token::LBrace => depth += 1,
+        Token::RBrace if depth == 0 => {
+          tokens.push(Spanned {
+            token: Token::Eof,
+            ..spanned
+          });
+          return Ok(tokens);
+        }
+        Token::RBrace => depth -= 1,
+        _ => {}
+      }
+      tokens.push(spanned);
+    }
+  }
+
+  // /.../i のiだけをflagとして受け付ける
+  fn regex(&mut self) -> Result<Token, DslError> {
+    let pos = self.pos;
+    self.bump();
+    let mut source = String::new();
+    loop {
+      match self.bump() {
+        None | Some('\n') => return self.error(pos, "unterminated regexp"),
+        Some('/') => break,
+        Some('\\') if self.peek() == Some('/') => source.push(self.bump().unwrap()),
+        Some('\\') => {
+          source.push('\\');
+          if let Some(c) = self.bump() {
+            source.push(c);
+          }
+        }
+        Some(c) => source.push(c),
+      }
+    }
+    while let Some(flag) = self.peek() {
+      match flag {
+        'i' => source.insert_str(0, "(?i)"),
+        c if c.is_ascii_alphabetic() => {
+          return self.error(self.pos, format!("unsupported regexp flag `{}`", c))
+        }
+        _ => break,
+      }
+      self.bump();
+    }
+    Ok(Token::Regex(source))
+  }
+
+  fn words(&mut self) -> Result<Token, DslError> {
+    let pos = self.pos;
+    self.bump();
+    self.bump();
+    let close = match self.bump() {
+      Some('[') => ']',
+      Some('(') => ')',
+      Some('{') => '}',
+      Some('<') => '>',
+      _ => return self.error(pos, "expected delimiter after %w"),
+    };
+    let mut words = vec![];
+    let mut word = String::new();
+    loop {
+      match self.bump() {
+        None => return self.error(pos, "unterminated %w"),
+        Some(c) if c == close => break,
+        Some(c) if c.is_whitespace() => {
+          if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+          }
+        }
+        Some(c) => word.push(c),
+      }
+    }
+    if !word.is_empty() {
+      words.push(word);
+    }
+    Ok(Token::Words(words))
+  }
+}
+
+fn is_ident_start(c: char) -> bool {
+  c.is_ascii_alphabetic() || c == '_'
+}
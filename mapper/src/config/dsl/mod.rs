@@ -0,0 +1,42 @@
+// xremapのRuby DSLのうち、rumapで使う部分をrubyなしで読む
+mod evaluator;
+mod lexer;
+mod syntax;
+
+pub use evaluator::*;
+pub use lexer::*;
+pub use syntax::*;
+
+// 1から数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+  pub line: usize,
+  pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DslError {
+  pub pos: Pos,
+  pub message: String,
+}
+
+impl DslError {
+  pub fn new(pos: Pos, message: impl Into<String>) -> Self {
+    Self {
+      pos,
+      message: message.into(),
+    }
+  }
+}
+
+impl std::fmt::Display for DslError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}: {}", self.pos.line, self.pos.column, self.message)
+  }
+}
+
+impl std::error::Error for DslError {}
+
+pub fn parse_dsl(source: &str) -> Result<super::Config, DslError> {
+  Evaluator::new().evaluate(&parse(source)?)
+}
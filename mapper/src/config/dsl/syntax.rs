@@ -0,0 +1,426 @@
+use super::*;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone)]
+pub struct Node {
+  pub expr: Expr,
+  pub pos: Pos,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+  Nil,
+  Bool(bool),
+  Int(i64),
+  Str(Vec<StrSegment>),
+  Regex(String),
+  Array(Vec<Node>),
+  Range(Box<Node>, Box<Node>),
+  Var(String),
+  Assign(String, Box<Node>),
+  Call(Call),
+}
+
+#[derive(Debug, Clone)]
+pub enum StrSegment {
+  Literal(String),
+  Interpolation(Node),
+}
+
+#[derive(Debug, Clone)]
+pub struct Call {
+  pub receiver: Option<Box<Node>>,
+  pub name: String,
+  pub args: Vec<Node>,
+  pub kwargs: Vec<(String, Node)>,
+  pub block: Option<Block>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+  pub params: Vec<String>,
+  pub body: Vec<Node>,
+}
+
+// 位置引数とkeyword引数
+type Args = (Vec<Node>, Vec<(String, Node)>);
+
+pub fn parse(source: &str) -> Result<Vec<Node>, DslError> {
+  let mut parser = Parser::new(tokenize(source)?);
+  parser.program()
+}
+
+struct Parser {
+  tokens: Vec<Spanned>,
+  index: usize,
+  // 代入やblock引数で定義されたlocal変数。rubyと同じく、変数でない識別子はmethod呼び出しとして扱う
+  locals: Vec<BTreeSet<String>>,
+}
+
+impl Parser {
+  fn new(tokens: Vec<Spanned>) -> Self {
+    Self {
+      tokens,
+      index: 0,
+      locals: vec![BTreeSet::new()],
+    }
+  }
+
+  fn peek(&self) -> &Spanned {
+    &self.tokens[self.index.min(self.tokens.len() - 1)]
+  }
+
+  fn peek_at(&self, offset: usize) -> &Spanned {
+    &self.tokens[(self.index + offset).min(self.tokens.len() - 1)]
+  }
+
+  fn bump(&mut self) -> Spanned {
+    let spanned = self.peek().clone();
+    if spanned.token != Token::Eof {
+      self.index += 1;
+    }
+    spanned
+  }
+
+  fn is_keyword(&self, keyword: &str) -> bool {
+    matches!(&self.peek().token, Token::Ident(name) if name == keyword)
+  }
+
+  fn skip_newlines(&mut self) {
+    while self.peek().token == Token::Newline {
+      self.bump();
+    }
+  }
+
+  fn expect(&mut self, token: Token, description: &str) -> Result<Spanned, DslError> {
+    if self.peek().token == token {
+      Ok(self.bump())
+    } else {
+      self.unexpected(description)
+    }
+  }
+
+  fn unexpected<T>(&self, expected: &str) -> Result<T, DslError> {
+    let spanned = self.peek();
+    Err(DslError::new(
+      spanned.pos,
+      format!("expected {}, found {}", expected, describe(&spanned.token)),
+    ))
+  }
+
+  fn is_local(&self, name: &str) -> bool {
+    self.locals.iter().any(|scope| scope.contains(name))
+  }
+
+  fn program(&mut self) -> Result<Vec<Node>, DslError> {
+    let statements = self.statements(|token| *token == Token::Eof)?;
+    self.expect(Token::Eof, "end of file")?;
+    Ok(statements)
+  }
+
+  // is_endにあたるtokenの手前までの文。is_endにあたるtokenは読まない
+  fn statements(&mut self, is_end: impl Fn(&Token) -> bool) -> Result<Vec<Node>, DslError> {
+    let mut statements = vec![];
+    loop {
+      self.skip_newlines();
+      if is_end(&self.peek().token) || self.peek().token == Token::Eof {
+        return Ok(statements);
+      }
+      statements.push(self.statement()?);
+      if !is_end(&self.peek().token) {
+        self.expect(Token::Newline, "end of statement")?;
+      }
+    }
+  }
+
+  fn statement(&mut self) -> Result<Node, DslError> {
+    if let (Token::Ident(name), Token::Assign) = (&self.peek().token, &self.peek_at(1).token) {
+      let name = name.clone();
+      let pos = self.bump().pos;
+      self.bump();
+      self.skip_newlines();
+      let value = self.expr(true)?;
+      self.locals.last_mut().unwrap().insert(name.clone());
+      return Ok(Node {
+        expr: Expr::Assign(name, Box::new(value)),
+        pos,
+      });
+    }
+    self.expr(true)
+  }
+
+  // allow_do: do...end を直前のmethod呼び出しのblockとして読むか
+  // rubyでは括弧なしの引数の中ではdoは外側の呼び出しにつくので、そのときはfalseにする
+  fn expr(&mut self, allow_do: bool) -> Result<Node, DslError> {
+    let start = self.postfix(allow_do)?;
+    if self.peek().token != Token::DotDot {
+      return Ok(start);
+    }
+    self.bump();
+    let end = self.postfix(allow_do)?;
+    let pos = start.pos;
+    Ok(Node {
+      expr: Expr::Range(Box::new(start), Box::new(end)),
+      pos,
+    })
+  }
+
+  fn postfix(&mut self, allow_do: bool) -> Result<Node, DslError> {
+    let mut node = self.primary(allow_do)?;
+    while self.peek().token == Token::Dot {
+      self.bump();
+      let spanned = self.bump();
+      let name = match spanned.token {
+        Token::Ident(name) => name,
+        _ => {
+          self.index -= 1;
+          return self.unexpected("method name");
+        }
+      };
+      let pos = node.pos;
+      node = self.call(Some(node), name, pos, allow_do)?;
+    }
+    Ok(node)
+  }
+
+  fn primary(&mut self, allow_do: bool) -> Result<Node, DslError> {
+    let spanned = self.bump();
+    let pos = spanned.pos;
+    let expr = match spanned.token {
+      Token::Int(value) => Expr::Int(value),
+      Token::Regex(source) => Expr::Regex(source),
+      Token::Words(words) => Expr::Array(
+        words
+          .into_iter()
+          .map(|word| Node {
+            expr: Expr::Str(vec![StrSegment::Literal(word)]),
+            pos,
+          })
+          .collect(),
+      ),
+      Token::Str(parts) => Expr::Str(self.string(parts)?),
+      Token::LBracket => Expr::Array(self.array()?),
+      Token::LParen => {
+        self.skip_newlines();
+        let node = self.expr(true)?;
+        self.skip_newlines();
+        self.expect(Token::RParen, "`)`")?;
+        return Ok(node);
+      }
+      Token::Ident(name) => match name.as_str() {
+        "nil" => Expr::Nil,
+        "true" => Expr::Bool(true),
+        "false" => Expr::Bool(false),
+        "do" | "end" => {
+          self.index -= 1;
+          return self.unexpected("expression");
+        }
+        _ if self.is_local(&name) && !self.has_paren_args() => Expr::Var(name),
+        _ => return self.call(None, name, pos, allow_do),
+      },
+      _ => {
+        self.index -= 1;
+        return self.unexpected("expression");
+      }
+    };
+    Ok(Node { expr, pos })
+  }
+
+  fn string(&mut self, parts: Vec<StrPart>) -> Result<Vec<StrSegment>, DslError> {
+    parts
+      .into_iter()
+      .map(|part| match part {
+        StrPart::Literal(literal) => Ok(StrSegment::Literal(literal)),
+        StrPart::Interpolation(tokens) => {
+          // 変数はそのまま見えるようにする
+          let mut parser = Parser {
+            tokens,
+            index: 0,
+            locals: self.locals.clone(),
+          };
+          let node = parser.expr(true)?;
+          parser.expect(Token::Eof, "`}`")?;
+          Ok(StrSegment::Interpolation(node))
+        }
+      })
+      .collect()
+  }
+
+  fn array(&mut self) -> Result<Vec<Node>, DslError> {
+    let mut elements = vec![];
+    loop {
+      self.skip_newlines();
+      if self.peek().token == Token::RBracket {
+        self.bump();
+        return Ok(elements);
+      }
+      elements.push(self.expr(true)?);
+      self.skip_newlines();
+      if self.peek().token != Token::RBracket {
+        self.expect(Token::Comma, "`,` or `]`")?;
+      }
+    }
+  }
+
+  fn has_paren_args(&self) -> bool {
+    let next = self.peek();
+    next.token == Token::LParen && !next.space_before
+  }
+
+  // 括弧なしの引数が始まっているか
+  fn has_command_args(&self) -> bool {
+    let next = self.peek();
+    if !next.space_before {
+      return false;
+    }
+    match &next.token {
+      Token::Int(_)
+      | Token::Str(_)
+      | Token::Regex(_)
+      | Token::Words(_)
+      | Token::Label(_)
+      | Token::LBracket
+      | Token::LParen => true,
+      Token::Ident(name) => name != "do" && name != "end",
+      _ => false,
+    }
+  }
+
+  fn call(
+    &mut self,
+    receiver: Option<Node>,
+    name: String,
+    pos: Pos,
+    allow_do: bool,
+  ) -> Result<Node, DslError> {
+    let args = if self.has_paren_args() {
+      self.bump();
+      let args = self.args(true)?;
+      self.expect(Token::RParen, "`)`")?;
+      args
+    } else if receiver.is_none() && self.has_command_args() {
+      self.args(false)?
+    } else {
+      (vec![], vec![])
+    };
+    self.call_with_block(receiver, name, args, pos, allow_do)
+  }
+
+  fn call_with_block(
+    &mut self,
+    receiver: Option<Node>,
+    name: String,
+    (args, kwargs): Args,
+    pos: Pos,
+    allow_do: bool,
+  ) -> Result<Node, DslError> {
+    let block = if allow_do && self.is_keyword("do") {
+      self.bump();
+      let block = self.block(|token| matches!(token, Token::Ident(name) if name == "end"))?;
+      self.expect(Token::Ident("end".to_string()), "`end`")?;
+      Some(block)
+    } else if self.peek().token == Token::LBrace {
+      self.bump();
+      let block = self.block(|token| *token == Token::RBrace)?;
+      self.expect(Token::RBrace, "`}`")?;
+      Some(block)
+    } else {
+      None
+    };
+    Ok(Node {
+      expr: Expr::Call(Call {
+        receiver: receiver.map(Box::new),
+        name,
+        args,
+        kwargs,
+        block,
+      }),
+      pos,
+    })
+  }
+
+  fn block(&mut self, is_end: impl Fn(&Token) -> bool) -> Result<Block, DslError> {
+    let mut params = vec![];
+    self.skip_newlines();
+    if self.peek().token == Token::Pipe {
+      self.bump();
+      loop {
+        match self.bump().token {
+          Token::Ident(name) => params.push(name),
+          _ => {
+            self.index -= 1;
+            return self.unexpected("block parameter");
+          }
+        }
+        if self.peek().token == Token::Pipe {
+          self.bump();
+          break;
+        }
+        self.expect(Token::Comma, "`,` or `|`")?;
+      }
+    }
+    self.locals.push(params.iter().cloned().collect());
+    let body = self.statements(is_end);
+    self.locals.pop();
+    Ok(Block {
+      params,
+      body: body?,
+    })
+  }
+
+  // parenthesized: 括弧の中なら改行をまたげる
+  fn args(&mut self, parenthesized: bool) -> Result<Args, DslError> {
+    let mut args = vec![];
+    let mut kwargs = vec![];
+    loop {
+      if parenthesized {
+        self.skip_newlines();
+        if self.peek().token == Token::RParen {
+          return Ok((args, kwargs));
+        }
+      }
+      if let Token::Label(label) = &self.peek().token {
+        let label = label.clone();
+        self.bump();
+        self.skip_newlines();
+        kwargs.push((label, self.expr(parenthesized)?));
+      } else if kwargs.is_empty() {
+        args.push(self.expr(parenthesized)?);
+      } else {
+        return self.unexpected("keyword argument");
+      }
+      if parenthesized {
+        self.skip_newlines();
+      }
+      if self.peek().token != Token::Comma {
+        return Ok((args, kwargs));
+      }
+      self.bump();
+      self.skip_newlines();
+    }
+  }
+}
+
+fn describe(token: &Token) -> String {
+  match token {
+    Token::Ident(name) => format!("`{}`", name),
+    Token::Label(label) => format!("`{}:`", label),
+    Token::Str(_) => "string".to_string(),
+    Token::Int(value) => format!("`{}`", value),
+    Token::Regex(source) => format!("`/{}/`", source),
+    Token::Words(_) => "`%w`".to_string(),
+    Token::Comma => "`,`".to_string(),
+    Token::Dot => "`.`".to_string(),
+    Token::DotDot => "`..`".to_string(),
+    Token::LParen => "`(`".to_string(),
+    Token::RParen => "`)`".to_string(),
+    Token::LBracket => "`[`".to_string(),
+    Token::RBracket => "`]`".to_string(),
+    Token::LBrace => "`{`".to_string(),
+    Token::RBrace => "`}`".to_string(),
+    Token::Pipe => "`|`".to_string(),
+    Token::Assign => "`=`".to_string(),
+    Token::Newline => "end of line".to_string(),
+    Token::Eof => "end of file".to_string(),
+  }
+}
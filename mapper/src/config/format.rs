@@ -0,0 +1,75 @@
+use super::*;
+use std::path::Path;
+
+// 設定ファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+  Json,
+  Toml,
+  Yaml,
+}
+
+// lineとcolumnは1から数える。位置がわからないときはNone
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatError {
+  pub format: ConfigFormat,
+  pub line: Option<usize>,
+  pub column: Option<usize>,
+  pub message: String,
+}
+
+impl std::fmt::Display for FormatError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match (self.line, self.column) {
+      (Some(line), Some(column)) => {
+        write!(f, "{:?} {}:{}: {}", self.format, line, column, self.message)
+      }
+      _ => write!(f, "{:?}: {}", self.format, self.message),
+    }
+  }
+}
+
+impl std::error::Error for FormatError {}
+
+impl ConfigFormat {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name.to_lowercase().as_str() {
+      "json" => Some(ConfigFormat::Json),
+      "toml" => Some(ConfigFormat::Toml),
+      "yaml" | "yml" => Some(ConfigFormat::Yaml),
+      _ => None,
+    }
+  }
+
+  pub fn from_extension(path: &Path) -> Option<Self> {
+    Self::from_name(path.extension()?.to_str()?)
+  }
+
+  pub fn parse(self, source: &str) -> Result<Config, FormatError> {
+    let error = |position: Option<(usize, usize)>, message: String| FormatError {
+      format: self,
+      line: position.map(|(line, _)| line),
+      column: position.map(|(_, column)| column),
+      message,
+    };
+    match self {
+      ConfigFormat::Json => serde_json::from_str(source)
+        .map_err(|err| error(Some((err.line(), err.column())), err.to_string())),
+      // tomlの位置は0から数える
+      ConfigFormat::Toml => toml::from_str(source).map_err(|err| {
+        error(
+          err.line_col().map(|(line, column)| (line + 1, column + 1)),
+          err.to_string(),
+        )
+      }),
+      ConfigFormat::Yaml => serde_yaml::from_str(source).map_err(|err| {
+        error(
+          err
+            .location()
+            .map(|location| (location.line(), location.column())),
+          err.to_string(),
+        )
+      }),
+    }
+  }
+}
@@ -11,6 +11,16 @@ pub trait IsIntoDomain<
     app: super::Application,
   ) -> Result<crate::Application<A>, super::InvalidConfigError>;
 
+  // globやregexに対応しないなら完全一致にする
+  fn into_domain_application_pattern(
+    &self,
+    app: super::Application,
+  ) -> Result<crate::ApplicationPattern<A>, super::InvalidConfigError> {
+    Ok(crate::ApplicationPattern::exact(
+      self.into_domain_application(app)?,
+    ))
+  }
+
   fn into_domain_keyinput(
     &self,
     key_input: super::KeyInput,
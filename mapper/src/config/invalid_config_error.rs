@@ -1,14 +1,163 @@
-#[derive(Debug)]
+use super::dsl::Pos;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InvalidConfigError {
   EmptyKey,
   UnexpectedKey(String),
   UnexpectedModifier(String),
+  InvalidApplicationPattern(String),
+  // "C--x" のように空のmodifierがある
+  EmptyModifier(String),
+  // "C-" のようにmodifierの後にkeyがない
+  MissingKey(String),
+  // "j++k" のように空のchordのkeyがある
+  EmptyChordKey(String),
+  // 設定のどこで起きたかわかっているエラー
+  Located(Location, Box<InvalidConfigError>),
+}
+
+impl InvalidConfigError {
+  // 場所を除いたエラーそのもの
+  pub fn kind(&self) -> &InvalidConfigError {
+    match self {
+      InvalidConfigError::Located(_, error) => error.kind(),
+      error => error,
+    }
+  }
+
+  pub fn location(&self) -> Option<&Location> {
+    match self {
+      InvalidConfigError::Located(location, _) => Some(location),
+      _ => None,
+    }
+  }
+
+  // 内側でわかった場所を優先するので、まだわかっていないものだけを埋める
+  pub fn on(self, side: Side) -> Self {
+    self.locate(|location| {
+      location.side.get_or_insert(side);
+    })
+  }
+
+  pub fn in_section(self, section: Section) -> Self {
+    self.locate(|location| {
+      location.section.get_or_insert(section);
+    })
+  }
+
+  pub fn in_remap(self, section: Section, from: String, pos: Option<Pos>) -> Self {
+    self.locate(|location| {
+      location.section.get_or_insert(section);
+      location.from.get_or_insert(from);
+      if location.pos.is_none() {
+        location.pos = pos;
+      }
+    })
+  }
+
+  fn locate(self, update: impl FnOnce(&mut Location)) -> Self {
+    let (mut location, error) = match self {
+      InvalidConfigError::Located(location, error) => (location, error),
+      error => (Location::default(), Box::new(error)),
+    };
+    update(&mut location);
+    InvalidConfigError::Located(location, error)
+  }
 }
 
 impl std::fmt::Display for InvalidConfigError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{:?}", self)
+    match self {
+      InvalidConfigError::EmptyKey => write!(f, "empty key"),
+      InvalidConfigError::UnexpectedKey(key) => write!(f, "unknown key `{}`", key),
+      InvalidConfigError::UnexpectedModifier(modifier) => {
+        write!(f, "unknown modifier `{}`", modifier)
+      }
+      InvalidConfigError::InvalidApplicationPattern(pattern) => {
+        write!(f, "invalid application pattern `{}`", pattern)
+      }
+      InvalidConfigError::EmptyModifier(key_input) => {
+        write!(f, "empty modifier in `{}`", key_input)
+      }
+      InvalidConfigError::MissingKey(key_input) => {
+        write!(f, "missing key after modifiers in `{}`", key_input)
+      }
+      InvalidConfigError::EmptyChordKey(key_input) => {
+        write!(f, "empty chord key in `{}`", key_input)
+      }
+      InvalidConfigError::Located(location, error) => write!(f, "{}: {}", location, error),
+    }
   }
 }
 
 impl std::error::Error for InvalidConfigError {}
+
+// 設定のどのsectionで起きたエラーか
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+  Remap,
+  InApp(String),
+  Mode(String),
+  Window(usize),
+  Priority,
+  IgnoreModifiers,
+}
+
+impl std::fmt::Display for Section {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Section::Remap => write!(f, "remap"),
+      Section::InApp(app) => write!(f, "in_app[{}]", app),
+      Section::Mode(mode) => write!(f, "modes[{}]", mode),
+      Section::Window(index) => write!(f, "windows[{}]", index),
+      Section::Priority => write!(f, "priority"),
+      Section::IgnoreModifiers => write!(f, "ignore_modifiers"),
+    }
+  }
+}
+
+// remapのどちら側で起きたエラーか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+  From,
+  To,
+  With,
+}
+
+impl std::fmt::Display for Side {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Side::From => write!(f, "from"),
+      Side::To => write!(f, "to"),
+      Side::With => write!(f, "with"),
+    }
+  }
+}
+
+// fromはエラーが起きたremapのkey。posは設定fileの位置がわかるときだけ
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Location {
+  pub section: Option<Section>,
+  pub from: Option<String>,
+  pub side: Option<Side>,
+  pub pos: Option<Pos>,
+}
+
+impl std::fmt::Display for Location {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut parts = vec![];
+    if let Some(pos) = &self.pos {
+      parts.push(format!("{}:{}:", pos.line, pos.column));
+    }
+    if let Some(section) = &self.section {
+      parts.push(section.to_string());
+    }
+    if let Some(from) = &self.from {
+      parts.push(format!("`{}`", from));
+    }
+    if let Some(side) = &self.side {
+      parts.push(format!("({})", side));
+    }
+    write!(f, "{}", parts.join(" "))
+  }
+}
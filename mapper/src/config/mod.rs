@@ -1,8 +1,11 @@
+pub mod dsl;
+pub mod format;
 pub mod into_domain;
 pub mod invalid_config_error;
 pub mod parser;
 pub mod values;
 
+pub use format::*;
 pub use into_domain::*;
 pub use invalid_config_error::*;
 pub use parser::*;
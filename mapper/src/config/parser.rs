@@ -1,8 +1,15 @@
 use super::*;
 use crate::*;
-use itertools::Itertools;
 use std::collections::BTreeMap;
 use std::marker::PhantomData;
+use std::time::Duration;
+
+// tap/holdでholdとみなすまでのミリ秒
+pub const DEFAULT_HOLD_TIMEOUT: u64 = 200;
+// chordのkeyをすべて押すまでに待つミリ秒
+pub const DEFAULT_CHORD_TIMEOUT: u64 = 50;
+
+type KeyBinds<'a, K, M, C> = Vec<Box<dyn KeyBind<K, M, C> + 'a>>;
 
 pub trait IsParser<
   'a,
@@ -20,6 +27,8 @@ pub trait IsParser<
     &self,
     config: config::Config,
   ) -> Result<PossibleKeyinputFinder<A, K, M>, InvalidConfigError>;
+  // 最初のエラーで止めずに、すべてのremapを検証する
+  fn check(&self, config: config::Config) -> Vec<InvalidConfigError>;
 }
 
 pub struct Parser<
@@ -50,87 +59,227 @@ impl<
     &self,
     config: config::Config,
   ) -> Result<KeyBindForFocus<'a, A, K, M, C>, InvalidConfigError> {
-    let global_keybinds: Box<dyn KeyBind<K, M, C> + 'a> = Box::new(CompositKeyBind::new(
-      config
-        .remap
+    let chord_timeout =
+      Duration::from_millis(config.chord_timeout.unwrap_or(DEFAULT_CHORD_TIMEOUT));
+    let positions = config.positions;
+    let to_keybinds = |section: Section, remaps: super::Remaps| {
+      remaps
         .0
         .into_iter()
-        .map(|(from, action)| self.remap_to_keybind(from, action))
-        .collect::<Result<_, _>>()?,
+        .map(|(from, action)| {
+          self
+            .remap_to_keybind(from.clone(), action, chord_timeout)
+            .map_err(|error| locate_remap(&positions, &section, &from, error))
+        })
+        .collect::<Result<KeyBinds<'a, K, M, C>, _>>()
+    };
+    let global_keybinds: Box<dyn KeyBind<K, M, C> + 'a> = Box::new(CompositKeyBind::new(
+      to_keybinds(Section::Remap, config.remap)?,
     ));
 
     let mut keybinds_for_applications: BTreeMap<
       crate::Application<A>,
       Box<dyn KeyBind<K, M, C> + 'a>,
     > = BTreeMap::new();
+    // globやregexで指定されたin_appとwindowsは、同じ条件のものをまとめる
+    let mut keybinds_for_matchers: BTreeMap<crate::ApplicationMatcher<A>, KeyBinds<'a, K, M, C>> =
+      BTreeMap::new();
     for (app, remaps) in config.in_app.into_iter() {
-      let keybinds: Vec<Box<dyn KeyBind<K, M, C> + 'a>> = remaps
-        .0
-        .into_iter()
-        .map(|(from, action)| self.remap_to_keybind(from, action))
-        .collect::<Result<_, _>>()?;
+      let section = Section::InApp(app.0.clone());
+      let keybinds = to_keybinds(section.clone(), remaps)?;
       let composit_keybind: Box<dyn KeyBind<K, M, C> + 'a> =
         Box::new(CompositKeyBind::new(keybinds));
 
-      keybinds_for_applications.insert(
-        self.into_domain.into_domain_application(app)?,
-        composit_keybind,
+      match self
+        .in_app_layer(app)
+        .map_err(|error| error.in_section(section))?
+      {
+        Layer::Matcher(matcher) => keybinds_for_matchers
+          .entry(matcher)
+          .or_default()
+          .push(composit_keybind),
+        Layer::Application(application) => {
+          keybinds_for_applications.insert(application, composit_keybind);
+        }
+        _ => {}
+      }
+    }
+
+    let mut keybinds_for_modes: BTreeMap<crate::Mode, Box<dyn KeyBind<K, M, C> + 'a>> =
+      BTreeMap::new();
+    for (mode, remaps) in config.modes.into_iter() {
+      let keybinds = to_keybinds(Section::Mode(mode.0.clone()), remaps)?;
+      keybinds_for_modes.insert(
+        crate::Mode::new(mode.0),
+        Box::new(CompositKeyBind::new(keybinds)),
       );
     }
 
-    Ok(KeyBindForFocus::new(
-      global_keybinds,
-      keybinds_for_applications,
-    ))
+    let precedence = self.build_precedence(config.priority, &config.windows)?;
+    for (index, window) in config.windows.into_iter().enumerate() {
+      let keybinds = to_keybinds(Section::Window(index), window.remap)?;
+      keybinds_for_matchers
+        .entry(
+          self
+            .to_domain_matcher(window.application)
+            .map_err(|error| error.in_section(Section::Window(index)))?,
+        )
+        .or_default()
+        .extend(keybinds);
+    }
+    let keybinds_for_matchers = keybinds_for_matchers
+      .into_iter()
+      .map(|(matcher, keybinds)| {
+        let keybind: Box<dyn KeyBind<K, M, C> + 'a> = Box::new(CompositKeyBind::new(keybinds));
+        (matcher, keybind)
+      })
+      .collect();
+
+    Ok(
+      KeyBindForFocus::new(global_keybinds, keybinds_for_applications)
+        .with_modes(keybinds_for_modes)
+        .with_matchers(keybinds_for_matchers)
+        .with_precedence(precedence),
+    )
   }
 
   fn build_possible_keyinput_finder(
     &self,
     config: config::Config,
   ) -> Result<PossibleKeyinputFinder<A, K, M>, InvalidConfigError> {
-    let mut cache = BTreeMap::<crate::Focus<A>, Vec<crate::KeyInput<K, M>>>::new();
+    let mut cache = SequenceCache::<A, K, M>::new();
+    let mut disabled = SequenceCache::<A, K, M>::new();
+    let positions = config.positions;
+    let mut register = |section: Section, layer: Layer<A>, remaps: super::Remaps| {
+      for (from_config, action) in remaps.0.into_iter() {
+        self
+          .register_possible_keyinputs(
+            &mut cache,
+            &mut disabled,
+            layer.clone(),
+            from_config.clone(),
+            action,
+          )
+          .map_err(|error| locate_remap(&positions, &section, &from_config, error))?;
+      }
+      Ok(())
+    };
 
     // global remap
-    for (from_config, action) in config.remap.0.into_iter() {
-      let possible_modifiers = match action {
-        config::values::Action::KeyInput { to: _, with } => with.unwrap_or_default(),
-        _ => vec![],
-      };
-      let possible_keyinputs =
-        self.possible_modifiers_to_keyinputs(from_config, possible_modifiers)?;
-      cache
-        .entry(Focus::NoFocus)
-        .and_modify(|keyinputs| keyinputs.extend(possible_keyinputs.clone()))
-        .or_insert(possible_keyinputs);
-    }
+    register(Section::Remap, Layer::Global, config.remap)?;
 
     // application specific remap
     for (app, remap) in config.in_app.into_iter() {
-      let focus = Focus::Focused {
-        application: self.into_domain.into_domain_application(app)?,
-      };
-      for (from_config, action) in remap.0.clone().into_iter() {
-        let possible_modifiers = match action {
-          config::values::Action::KeyInput { to: _, with } => with.unwrap_or_default(),
-          _ => vec![],
+      let section = Section::InApp(app.0.clone());
+      let layer = self
+        .in_app_layer(app)
+        .map_err(|error| error.in_section(section.clone()))?;
+      register(section, layer, remap)?;
+    }
+
+    // mode specific remap
+    for (mode, remap) in config.modes.into_iter() {
+      let layer = Layer::Mode(crate::Mode::new(mode.0.clone()));
+      register(Section::Mode(mode.0), layer, remap)?;
+    }
+
+    // class_only / class_not で指定されたremap
+    let precedence = self.build_precedence(config.priority, &config.windows)?;
+    for (index, window) in config.windows.into_iter().enumerate() {
+      let layer = Layer::Matcher(
+        self
+          .to_domain_matcher(window.application)
+          .map_err(|error| error.in_section(Section::Window(index)))?,
+      );
+      register(Section::Window(index), layer, window.remap)?;
+    }
+
+    Ok(
+      PossibleKeyinputFinder::with_sequences(cache)
+        .with_disabled(disabled)
+        .with_precedence(precedence)
+        .with_ignored_modifiers(
+          self
+            .modifiers(config.ignore_modifiers)
+            .map_err(|error| error.in_section(Section::IgnoreModifiers))?,
+        ),
+    )
+  }
+
+  fn check(&self, config: config::Config) -> Vec<InvalidConfigError> {
+    let chord_timeout =
+      Duration::from_millis(config.chord_timeout.unwrap_or(DEFAULT_CHORD_TIMEOUT));
+    let positions = config.positions;
+    let mut errors = vec![];
+    let mut check_remaps = |section: Section, remaps: super::Remaps| {
+      for (from, action) in remaps.0.into_iter() {
+        let remap_errors = self.remap_errors(from.clone(), action.clone());
+        // fromやtoのそれぞれに誤りがなければ、組み立てたときの誤りを探す
+        let result = if remap_errors.is_empty() {
+          let mut cache = BTreeMap::new();
+          let mut disabled = BTreeMap::new();
+          self
+            .remap_to_keybind(from.clone(), action.clone(), chord_timeout)
+            .and_then(|_| {
+              self.register_possible_keyinputs(
+                &mut cache,
+                &mut disabled,
+                Layer::Global,
+                from.clone(),
+                action,
+              )
+            })
+            .err()
+            .into_iter()
+            .collect()
+        } else {
+          remap_errors
         };
-        let possible_keyinputs =
-          self.possible_modifiers_to_keyinputs(from_config, possible_modifiers)?;
-        cache
-          .entry(focus.clone())
-          .and_modify(|keyinputs| keyinputs.extend(possible_keyinputs.clone()))
-          .or_insert(possible_keyinputs);
+        errors.extend(
+          result
+            .into_iter()
+            .map(|error| locate_remap(&positions, &section, &from, error)),
+        );
       }
+    };
+
+    check_remaps(Section::Remap, config.remap);
+    let mut section_errors = vec![];
+    for (app, remaps) in config.in_app.into_iter() {
+      let section = Section::InApp(app.0.clone());
+      if let Err(error) = self.in_app_layer(app) {
+        section_errors.push(error.in_section(section.clone()));
+      }
+      check_remaps(section, remaps);
+    }
+    for (mode, remaps) in config.modes.into_iter() {
+      check_remaps(Section::Mode(mode.0), remaps);
+    }
+    for (index, window) in config.windows.into_iter().enumerate() {
+      if let Err(error) = self.to_domain_matcher(window.application) {
+        section_errors.push(error.in_section(Section::Window(index)));
+      }
+      check_remaps(Section::Window(index), window.remap);
+    }
+    for (app, _) in config.priority.in_app.into_iter() {
+      if let Err(error) = self.in_app_layer(app) {
+        section_errors.push(error.in_section(Section::Priority));
+      }
+    }
+
+    if let Err(error) = self.modifiers(config.ignore_modifiers) {
+      section_errors.push(error.in_section(Section::IgnoreModifiers));
     }
 
-    Ok(PossibleKeyinputFinder::new(cache))
+    errors.extend(section_errors);
+    errors
   }
 }
 
 impl<
     'a,
     A: PartialEq + Eq + PartialOrd + Ord + Clone + 'a,
-    K: PartialEq + Eq + std::fmt::Debug + Clone + 'a,
+    K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone + 'a,
     M: PartialOrd + Ord + std::fmt::Debug + Clone + 'a,
     C: std::fmt::Debug + Clone + 'a,
     ID: IsIntoDomain<'a, A, K, M, C>,
@@ -146,45 +295,395 @@ impl<
     }
   }
 
-  fn possible_modifiers_to_keyinputs(
+  // withのmodifierは押されていてもいなくてもよい。どの組み合わせをgrabするかはevent sourceに任せる
+  fn possible_modifiers_to_grab(
     &self,
     base: super::KeyInput,
     possible_modifiers: Vec<super::Modifier>,
-  ) -> Result<Vec<crate::KeyInput<K, M>>, InvalidConfigError> {
-    let base = self.into_domain.into_domain_keyinput(base)?;
-    let mut keyinputs = vec![];
-    for modifier_length in 0..=possible_modifiers.len() {
-      for additional_modifiers in possible_modifiers
-        .clone()
+  ) -> Result<Grab<K, M>, InvalidConfigError> {
+    let base = self.keyinput(base, Side::From)?;
+    let possible_modifiers = self
+      .modifiers(possible_modifiers)
+      .map_err(|error| error.on(Side::With))?;
+    Ok(Grab::new(base).with_optional_modifiers(possible_modifiers))
+  }
+
+  fn modifiers(&self, modifiers: Vec<super::Modifier>) -> Result<Modifiers<M>, InvalidConfigError> {
+    Ok(Modifiers::new(
+      modifiers
         .into_iter()
-        .combinations(modifier_length)
-      {
-        keyinputs.push(
-          base.clone().merge_modifiers(&Modifiers::new(
-            additional_modifiers
-              .into_iter()
-              .map(|m| self.into_domain.into_domain_modifier(m))
-              .collect::<Result<Vec<crate::Modifier<M>>, _>>()?,
-          )),
-        )
-      }
-    }
-    Ok(keyinputs)
+        .map(|m| self.into_domain.into_domain_modifier(m))
+        .collect::<Result<Vec<crate::Modifier<M>>, _>>()?,
+    ))
   }
 
   fn remap_to_keybind(
     &self,
     from: super::KeyInput,
     action: super::Action,
+    chord_timeout: Duration,
   ) -> Result<Box<dyn KeyBind<K, M, C> + 'a>, InvalidConfigError> {
-    match action {
-      super::Action::KeyInput { to, with: _ } => Ok(Box::new(crate::Keymap::new(
-        self.into_domain.into_domain_keyinput(from)?,
-        self.into_domain.into_domain_keyinput(to)?,
-      ))),
-      super::Action::Execution { execute: execution } => {
-        self.into_domain.into_domain_action(from, execution)
+    let mut strokes = split_strokes(from);
+    let chord_keys = split_chord(strokes.pop().unwrap())?;
+    // chordの場合、最後のkeyが押されたものとしてkeybindを作る
+    let from = chord_keys.last().unwrap().clone();
+    let keybind: Box<dyn KeyBind<K, M, C> + 'a> = match action {
+      super::Action::KeyInput { to, with: _ } => Box::new(crate::Keymap::new(
+        self.keyinput(from, Side::From)?,
+        self.keyinput(to, Side::To)?,
+      )),
+      super::Action::Execution { execute: execution } => check_keyinput(&from)
+        .and_then(|_| self.into_domain.into_domain_action(from, execution))
+        .map_err(|error| error.on(Side::From))?,
+      super::Action::EnterMode { enter_mode } => Box::new(ActionKeyBind::new(
+        self.keyinput(from, Side::From)?,
+        crate::Action::EnterMode {
+          mode: crate::Mode::new(enter_mode.0),
+        },
+      )),
+      super::Action::ExitMode { exit_mode } => Box::new(ActionKeyBind::new(
+        self.keyinput(from, Side::From)?,
+        crate::Action::ExitMode {
+          mode: crate::Mode::new(exit_mode.0),
+        },
+      )),
+      super::Action::ToggleMode { toggle_mode } => Box::new(ActionKeyBind::new(
+        self.keyinput(from, Side::From)?,
+        crate::Action::ToggleMode {
+          mode: crate::Mode::new(toggle_mode.0),
+        },
+      )),
+      super::Action::TapHold {
+        tap,
+        hold,
+        hold_timeout,
+      } => Box::new(ActionKeyBind::new(
+        self.keyinput(from, Side::From)?,
+        crate::Action::TapHold {
+          tap: self.keyinput(tap, Side::To)?,
+          hold: self
+            .into_domain
+            .into_domain_modifier(hold)
+            .map_err(|error| error.on(Side::To))?,
+          timeout: Duration::from_millis(hold_timeout.unwrap_or(DEFAULT_HOLD_TIMEOUT)),
+        },
+      )),
+      super::Action::Disable { disable: true } => Box::new(ActionKeyBind::new(
+        self.keyinput(from, Side::From)?,
+        crate::Action::Disabled,
+      )),
+      super::Action::Disable { disable: false } => Box::new(CompositKeyBind::new(vec![])),
+      super::Action::Sequence { sequence } => Box::new(ActionKeyBind::new(
+        self.keyinput(from, Side::From)?,
+        crate::Action::Sequence {
+          steps: sequence
+            .into_iter()
+            .map(|step| self.to_domain_step(step))
+            .collect::<Result<_, _>>()?,
+        },
+      )),
+    };
+    let keybind: Box<dyn KeyBind<K, M, C> + 'a> = if chord_keys.len() > 1 {
+      Box::new(ChordKeyBind::new(
+        self.strokes_to_keyinputs(chord_keys)?,
+        keybind,
+        chord_timeout,
+      ))
+    } else {
+      keybind
+    };
+
+    if strokes.is_empty() {
+      Ok(keybind)
+    } else {
+      Ok(Box::new(SequenceKeyBind::new(
+        self.strokes_to_keyinputs(strokes)?,
+        keybind,
+      )))
+    }
+  }
+
+  // from、to、withとsequenceの各stepを別々に確かめて、見つかった誤りをすべて返す
+  fn remap_errors(&self, from: super::KeyInput, action: super::Action) -> Vec<InvalidConfigError> {
+    let mut errors = vec![];
+    let mut push = |result: Result<(), InvalidConfigError>| {
+      if let Err(error) = result {
+        push_unique(&mut errors, error);
+      }
+    };
+    let mut strokes = split_strokes(from);
+    let mut keys = match split_chord(strokes.pop().unwrap()) {
+      Ok(chord_keys) => chord_keys,
+      Err(error) => {
+        push(Err(error));
+        vec![]
+      }
+    };
+    let last_key = keys.pop();
+    for stroke in strokes.into_iter().chain(keys) {
+      push(self.keyinput(stroke, Side::From).map(|_| ()));
+    }
+    match (action, last_key) {
+      (super::Action::Execution { execute }, Some(last_key)) => push(
+        check_keyinput(&last_key)
+          .and_then(|_| self.into_domain.into_domain_action(last_key, execute))
+          .map(|_| ())
+          .map_err(|error| error.on(Side::From)),
+      ),
+      (action, last_key) => {
+        if let Some(last_key) = last_key {
+          push(self.keyinput(last_key, Side::From).map(|_| ()));
+        }
+        match action {
+          super::Action::KeyInput { to, with } => {
+            push(self.keyinput(to, Side::To).map(|_| ()));
+            for modifier in with.unwrap_or_default() {
+              push(
+                self
+                  .modifiers(vec![modifier])
+                  .map(|_| ())
+                  .map_err(|error| error.on(Side::With)),
+              );
+            }
+          }
+          super::Action::TapHold { tap, hold, .. } => {
+            push(self.keyinput(tap, Side::To).map(|_| ()));
+            push(
+              self
+                .into_domain
+                .into_domain_modifier(hold)
+                .map(|_| ())
+                .map_err(|error| error.on(Side::To)),
+            );
+          }
+          super::Action::Sequence { sequence } => {
+            for step in sequence {
+              push(self.to_domain_step(step).map(|_| ()));
+            }
+          }
+          _ => {}
+        }
+      }
+    }
+    errors
+  }
+
+  fn build_precedence(
+    &self,
+    priority: super::Priority,
+    windows: &[super::Window],
+  ) -> Result<Precedence<A>, InvalidConfigError> {
+    let mut precedence = Precedence::new();
+    if let Some(global) = priority.remap {
+      precedence = precedence.with_priority(Layer::Global, global);
+    }
+    for (app, value) in priority.in_app.into_iter() {
+      let layer = self
+        .in_app_layer(app)
+        .map_err(|error| error.in_section(Section::Priority))?;
+      precedence = precedence.with_priority(layer, value);
+    }
+    for (mode, value) in priority.modes.into_iter() {
+      precedence = precedence.with_priority(Layer::Mode(crate::Mode::new(mode.0)), value);
+    }
+    for (index, window) in windows.iter().enumerate() {
+      if let Some(value) = window.priority {
+        let matcher = self
+          .to_domain_matcher(window.application.clone())
+          .map_err(|error| error.in_section(Section::Window(index)))?;
+        precedence = precedence.with_priority(Layer::Matcher(matcher), value);
       }
     }
+    Ok(precedence)
+  }
+
+  fn to_domain_matcher(
+    &self,
+    matcher: super::ApplicationMatcher,
+  ) -> Result<crate::ApplicationMatcher<A>, InvalidConfigError> {
+    let to_patterns = |applications: Vec<super::Application>| {
+      applications
+        .into_iter()
+        .map(|application| {
+          self
+            .into_domain
+            .into_domain_application_pattern(application)
+        })
+        .collect::<Result<Vec<_>, _>>()
+    };
+    let to_title_patterns = |titles: Vec<String>| {
+      titles
+        .into_iter()
+        .map(|title| {
+          TitlePattern::parse(title.clone())
+            .map_err(|_| InvalidConfigError::InvalidApplicationPattern(title))
+        })
+        .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(
+      crate::ApplicationMatcher::new(
+        matcher.class_only.map(to_patterns).transpose()?,
+        to_patterns(matcher.class_not)?,
+      )
+      .with_titles(
+        matcher.title_only.map(to_title_patterns).transpose()?,
+        to_title_patterns(matcher.title_not)?,
+      ),
+    )
+  }
+
+  // in_appは完全一致ならapplicationごとに、patternならmatcherとして登録する
+  fn in_app_layer(&self, app: super::Application) -> Result<Layer<A>, InvalidConfigError> {
+    Ok(
+      match self.into_domain.into_domain_application_pattern(app)? {
+        ApplicationPattern::Exact(application) => Layer::Application(application),
+        pattern => Layer::Matcher(crate::ApplicationMatcher::only(vec![pattern])),
+      },
+    )
+  }
+
+  fn to_domain_step(&self, step: super::Step) -> Result<crate::Step<K, M>, InvalidConfigError> {
+    Ok(match step {
+      super::Step::KeyInput(key_input) => crate::Step::new(self.keyinput(key_input, Side::To)?),
+      super::Step::Delayed { to, delay } => {
+        crate::Step::new(self.keyinput(to, Side::To)?).with_delay(Duration::from_millis(delay))
+      }
+    })
+  }
+
+  fn strokes_to_keyinputs(
+    &self,
+    strokes: Vec<super::KeyInput>,
+  ) -> Result<Vec<crate::KeyInput<K, M>>, InvalidConfigError> {
+    strokes
+      .into_iter()
+      .map(|stroke| self.keyinput(stroke, Side::From))
+      .collect()
+  }
+
+  fn keyinput(
+    &self,
+    key_input: super::KeyInput,
+    side: Side,
+  ) -> Result<crate::KeyInput<K, M>, InvalidConfigError> {
+    check_keyinput(&key_input)
+      .and_then(|_| self.into_domain.into_domain_keyinput(key_input))
+      .map_err(|error| error.on(side))
+  }
+}
+
+impl<
+    'a,
+    A: PartialEq + Eq + PartialOrd + Ord + Clone + 'a,
+    K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone + 'a,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone + 'a,
+    C: std::fmt::Debug + Clone + 'a,
+    ID: IsIntoDomain<'a, A, K, M, C>,
+  > Parser<'a, A, K, M, C, ID>
+{
+  // sequenceの場合、1stroke目は入力済みのstrokesが空のところに、2stroke目以降はそれまでのstrokesに対して登録する
+  fn register_possible_keyinputs(
+    &self,
+    cache: &mut SequenceCache<A, K, M>,
+    disabled: &mut SequenceCache<A, K, M>,
+    layer: Layer<A>,
+    from_config: super::KeyInput,
+    action: super::Action,
+  ) -> Result<(), InvalidConfigError> {
+    let (possible_modifiers, disable) = match action {
+      config::values::Action::KeyInput { to: _, with } => (with.unwrap_or_default(), false),
+      config::values::Action::Disable { disable } => (vec![], disable),
+      _ => (vec![], false),
+    };
+    let mut strokes = split_strokes(from_config);
+    let mut chord_keys = split_chord(strokes.pop().unwrap())?;
+    let last_stroke = chord_keys.pop().unwrap();
+    // chordはどのkeyから押されてもよいので、すべてのkeyをgrabする
+    let mut grabs: Vec<Grab<K, M>> = self
+      .strokes_to_keyinputs(chord_keys)?
+      .into_iter()
+      .map(Grab::new)
+      .collect();
+    grabs.push(self.possible_modifiers_to_grab(last_stroke, possible_modifiers)?);
+
+    let prefix = self.strokes_to_keyinputs(strokes)?;
+    for length in 0..prefix.len() {
+      push_unique(
+        cache
+          .entry((layer.clone(), prefix[..length].to_vec()))
+          .or_default(),
+        Grab::new(prefix[length].clone()),
+      );
+    }
+    // 無効にしたkeyinputはgrabせず、優先度の低いlayerでもgrabしないようにする
+    let following = if disable { disabled } else { cache }
+      .entry((layer, prefix))
+      .or_default();
+    for grab in grabs {
+      push_unique(following, grab);
+    }
+    Ok(())
+  }
+}
+
+// "C-x C-f" のように空白区切りで書かれたstrokeを分割する
+fn split_strokes(key_input: super::KeyInput) -> Vec<super::KeyInput> {
+  let strokes: Vec<super::KeyInput> = key_input
+    .0
+    .split_whitespace()
+    .map(|stroke| super::KeyInput(stroke.to_string()))
+    .collect();
+  if strokes.is_empty() {
+    vec![key_input]
+  } else {
+    strokes
+  }
+}
+
+// "j+k" のように+区切りで書かれたchordを分割する
+fn split_chord(stroke: super::KeyInput) -> Result<Vec<super::KeyInput>, InvalidConfigError> {
+  let keys: Vec<super::KeyInput> = stroke
+    .0
+    .split('+')
+    .map(|key| super::KeyInput(key.to_string()))
+    .collect();
+  if keys.len() == 1 {
+    Ok(keys)
+  } else if keys.iter().any(|key| key.0.is_empty()) {
+    Err(InvalidConfigError::EmptyChordKey(stroke.0).on(Side::From))
+  } else {
+    Ok(keys)
+  }
+}
+
+// Modifier-Modifier-...-Key の形になっているか
+fn check_keyinput(key_input: &super::KeyInput) -> Result<(), InvalidConfigError> {
+  let mut names: Vec<&str> = key_input.0.split('-').collect();
+  let key = names.pop().unwrap();
+  if names.iter().any(|name| name.is_empty()) {
+    Err(InvalidConfigError::EmptyModifier(key_input.0.clone()))
+  } else if !key.is_empty() {
+    Ok(())
+  } else if names.is_empty() {
+    Err(InvalidConfigError::EmptyKey)
+  } else {
+    Err(InvalidConfigError::MissingKey(key_input.0.clone()))
+  }
+}
+
+// remapで起きたエラーに、sectionとremapのkeyと、わかれば書かれた位置をつける
+fn locate_remap(
+  positions: &super::Positions,
+  section: &Section,
+  from: &super::KeyInput,
+  error: InvalidConfigError,
+) -> InvalidConfigError {
+  let pos = positions.get(&(section.clone(), from.clone())).cloned();
+  error.in_remap(section.clone(), from.0.clone(), pos)
+}
+
+fn push_unique<T: PartialEq>(values: &mut Vec<T>, value: T) {
+  if !values.contains(&value) {
+    values.push(value);
   }
 }
@@ -2,20 +2,41 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(transparent)]
 pub struct Application(pub String);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(transparent)]
 pub struct KeyInput(pub String);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(transparent)]
 pub struct Key(pub String);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(transparent)]
 pub struct Modifier(pub String);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(transparent)]
 pub struct Execution(pub String);
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(transparent)]
+pub struct Mode(pub String);
+
+// sequenceの1stroke分。待ち時間を指定しないときはstringだけで書ける
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Step {
+  KeyInput(KeyInput),
+  Delayed {
+    to: KeyInput,
+    // ミリ秒
+    delay: u64,
+  },
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Action {
@@ -26,13 +47,87 @@ pub enum Action {
   Execution {
     execute: Execution,
   },
+  EnterMode {
+    enter_mode: Mode,
+  },
+  ExitMode {
+    exit_mode: Mode,
+  },
+  ToggleMode {
+    toggle_mode: Mode,
+  },
+  TapHold {
+    tap: KeyInput,
+    hold: Modifier,
+    // ミリ秒
+    hold_timeout: Option<u64>,
+  },
+  Sequence {
+    sequence: Vec<Step>,
+  },
+  // 優先度の低いremap(in_appならglobalのremap)を無効にする
+  Disable {
+    disable: bool,
+  },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Remaps(pub BTreeMap<KeyInput, Action>);
 
+// class_only、class_not、title_only、title_notは組み合わせられる。どれも指定しなければすべてのapplicationにmatchする
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ApplicationMatcher {
+  #[serde(default)]
+  pub class_only: Option<Vec<Application>>,
+  #[serde(default)]
+  pub class_not: Vec<Application>,
+  #[serde(default)]
+  pub title_only: Option<Vec<String>>,
+  #[serde(default)]
+  pub title_not: Vec<String>,
+}
+
+// 条件にmatchするapplicationでだけ有効なremap
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Window {
+  #[serde(flatten)]
+  pub application: ApplicationMatcher,
+  pub remap: Remaps,
+  #[serde(default)]
+  pub priority: Option<i32>,
+}
+
+// remapを探す優先度。大きいほど優先され、指定がなければ0
+// 同じ優先度のときは modes > in_app > windows > remap の順
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Priority {
+  #[serde(default)]
+  pub remap: Option<i32>,
+  #[serde(default)]
+  pub in_app: BTreeMap<Application, i32>,
+  #[serde(default)]
+  pub modes: BTreeMap<Mode, i32>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
   pub remap: Remaps,
   pub in_app: BTreeMap<Application, Remaps>,
+  #[serde(default)]
+  pub modes: BTreeMap<Mode, Remaps>,
+  // chordのkeyをすべて押すまでに待つミリ秒
+  #[serde(default)]
+  pub chord_timeout: Option<u64>,
+  #[serde(default)]
+  pub priority: Priority,
+  #[serde(default)]
+  pub windows: Vec<Window>,
+  // keybindを探すときに無視するmodifier。CapsLockなどが有効でもremapできるようにする
+  #[serde(default)]
+  pub ignore_modifiers: Vec<Modifier>,
+  // DSLから読んだときの、remapが書かれた位置
+  #[serde(skip)]
+  pub positions: Positions,
 }
+
+pub type Positions = BTreeMap<(super::Section, KeyInput), super::dsl::Pos>;
@@ -0,0 +1,139 @@
+use crate::*;
+
+// 設定したのに使われないkeybind
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict<
+  A: PartialEq + Eq + PartialOrd + Ord + Clone,
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+> {
+  // 同じlayerに同じ入力のkeybindが先にある
+  Duplicate {
+    layer: Layer<A>,
+    trigger: Trigger<K, M>,
+  },
+  // 同じlayerで先にある同じ具体性のkeybindが、この入力にもmatchしてしまう
+  Shadowed {
+    layer: Layer<A>,
+    trigger: Trigger<K, M>,
+    by: Trigger<K, M>,
+  },
+  // in_appやwindowsのkeybindが、そのapplicationではglobalのkeybindを隠す
+  ShadowsGlobal {
+    layer: Layer<A>,
+    trigger: Trigger<K, M>,
+    global: Trigger<K, M>,
+  },
+}
+
+// layerと、そのlayerのkeybindのtriggers。triggersは優先されるものから順に並べる
+pub type LayerTriggers<A, K, M> = (Layer<A>, Vec<Trigger<K, M>>);
+
+pub fn find_conflicts<
+  A: PartialEq + Eq + PartialOrd + Ord + Clone,
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+>(
+  triggers_by_layer: &[LayerTriggers<A, K, M>],
+  precedence: &Precedence<A>,
+) -> Vec<Conflict<A, K, M>> {
+  let mut conflicts = vec![];
+  for (layer, triggers) in triggers_by_layer {
+    for (index, trigger) in triggers.iter().enumerate() {
+      // modifierの多いkeybindが優先されるので、隠れるのはmodifierが同じときだけ
+      let covered = triggers[..index].iter().find(|earlier| {
+        earlier.covers(trigger) && earlier.modifier_count() >= trigger.modifier_count()
+      });
+      if let Some(earlier) = covered {
+        conflicts.push(if trigger.covers(earlier) {
+          Conflict::Duplicate {
+            layer: layer.clone(),
+            trigger: trigger.clone(),
+          }
+        } else {
+          Conflict::Shadowed {
+            layer: layer.clone(),
+            trigger: trigger.clone(),
+            by: earlier.clone(),
+          }
+        });
+        continue;
+      }
+      // sequenceの最初のstrokeやchordのkeyを、先にある別のkeybindが取ってしまう
+      let captured = triggers[..index].iter().find(|earlier| {
+        captures_stroke(earlier, trigger, true) || captures_stroke(trigger, earlier, true)
+      });
+      if let Some(earlier) = captured {
+        conflicts.push(Conflict::Shadowed {
+          layer: layer.clone(),
+          trigger: trigger.clone(),
+          by: earlier.clone(),
+        });
+      }
+    }
+  }
+
+  let global_triggers = triggers_by_layer
+    .iter()
+    .filter(|(layer, _)| *layer == Layer::Global)
+    .flat_map(|(_, triggers)| triggers.iter());
+  for global in global_triggers {
+    for (layer, triggers) in triggers_by_layer {
+      // modeは明示的に切り替えるので、globalを隠しても意図どおり
+      // priorityでglobalのほうを優先しているなら隠さない
+      let is_app_layer = matches!(layer, Layer::Application(_) | Layer::Matcher(_));
+      if !is_app_layer || precedence.priority(layer) < precedence.priority(&Layer::Global) {
+        continue;
+      }
+      // 上のlayerでmatchしたkeybindがあれば、globalのkeybindは問い合わせない
+      if let Some(trigger) = triggers.iter().find(|trigger| {
+        trigger.covers(global)
+          || captures_stroke(trigger, global, false)
+          || captures_stroke(global, trigger, true)
+      }) {
+        conflicts.push(Conflict::ShadowsGlobal {
+          layer: layer.clone(),
+          trigger: trigger.clone(),
+          global: global.clone(),
+        });
+      }
+    }
+  }
+  conflicts
+}
+
+// sequenceの最初のstrokeやchordのkeyのように、最後まで入力する前に押されるkey
+fn partial_strokes<
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+>(
+  trigger: &Trigger<K, M>,
+) -> Vec<&KeyInput<K, M>> {
+  if !trigger.prefix.is_empty() {
+    trigger.prefix.iter().take(1).collect()
+  } else if trigger.keys.len() > 1 {
+    trigger.keys.iter().collect()
+  } else {
+    vec![]
+  }
+}
+
+// otherの途中で押すkeyに、1 strokeのkeybindのtriggerが反応するか
+// same_modifiersなら、modifierの数も同じときだけにする。同じlayerではmodifierの多いほうが優先され、
+// modifierの少ないtriggerはその途中のkeyを取られてもほかの入力で反応できる
+fn captures_stroke<
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+>(
+  trigger: &Trigger<K, M>,
+  other: &Trigger<K, M>,
+  same_modifiers: bool,
+) -> bool {
+  if !trigger.prefix.is_empty() || trigger.keys.len() != 1 {
+    return false;
+  }
+  partial_strokes(other).into_iter().any(|stroke| {
+    trigger.covers(&Trigger::exact(stroke.clone()))
+      && (!same_modifiers || trigger.modifier_count() >= stroke.modifiers().to_vec().len())
+  })
+}
@@ -15,4 +15,10 @@ pub enum Event<
   ApplicationChanged {
     next_application: Option<Application<A>>,
   },
+  // next_untilで指定した時刻を過ぎた
+  Tick,
+  // 設定を読み直す
+  Reload,
+  // keyboardの配列が変わった。keycodeが変わるのでgrabし直す
+  KeymapChanged,
 }
@@ -1,4 +1,5 @@
 use crate::*;
+use std::time::Instant;
 
 pub trait IsEventSource<
   K: PartialEq + Eq + Clone + std::fmt::Debug,
@@ -6,7 +7,17 @@ pub trait IsEventSource<
   A: PartialEq + Eq + PartialOrd + Ord + Clone,
 >
 {
-  fn ungrab_keys(&self);
-  fn grab_keys(&self, key_inputs: Vec<KeyInput<K, M>>);
+  fn ungrab_keys(&self, grabs: Vec<Grab<K, M>>);
+  // optional_modifiersやignored_modifiersの組み合わせをどうgrabするかはevent sourceに任せる
+  fn grab_keys(&self, grabs: Vec<Grab<K, M>>);
   fn next(&self) -> Option<Event<K, M, A>>;
+
+  // deadlineまでにeventが来なければEvent::Tickを返す
+  fn next_until(&self, _deadline: Instant) -> Option<Event<K, M, A>> {
+    self.next()
+  }
+
+  // 全てのkeyinputを横取りする。tap/holdの判定中などに使う
+  fn grab_keyboard(&self) {}
+  fn ungrab_keyboard(&self) {}
 }
@@ -0,0 +1,51 @@
+use crate::*;
+
+// X serverなどに横取りしてもらう入力
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Grab<
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+> {
+  pub key_input: KeyInput<K, M>,
+  // withで指定された、押されていてもいなくてもよいmodifier
+  pub optional_modifiers: Modifiers<M>,
+  // LockやNumLockのように、keybindを探すときに無視するmodifier
+  pub ignored_modifiers: Modifiers<M>,
+}
+
+impl<K: PartialEq + Eq + std::fmt::Debug + Clone, M: PartialOrd + Ord + std::fmt::Debug + Clone>
+  Grab<K, M>
+{
+  pub fn new(key_input: KeyInput<K, M>) -> Self {
+    Self {
+      key_input,
+      optional_modifiers: Modifiers::new(vec![]),
+      ignored_modifiers: Modifiers::new(vec![]),
+    }
+  }
+
+  pub fn with_optional_modifiers(mut self, modifiers: Modifiers<M>) -> Self {
+    self.optional_modifiers = modifiers;
+    self
+  }
+
+  pub fn with_ignored_modifiers(mut self, modifiers: Modifiers<M>) -> Self {
+    self.ignored_modifiers = modifiers;
+    self
+  }
+
+  pub fn key(&self) -> Key<K> {
+    self.key_input.key()
+  }
+
+  // key_inputがこのgrabで横取りしたい入力か
+  pub fn covers(&self, key_input: &KeyInput<K, M>) -> bool {
+    match self
+      .key_input
+      .match_to(&key_input.remove_modifiers(&self.ignored_modifiers))
+    {
+      Matching::Remain(modifiers) => modifiers.is_subset(&self.optional_modifiers),
+      Matching::Unmatched => false,
+    }
+  }
+}
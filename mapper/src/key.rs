@@ -53,6 +53,14 @@ impl<K: PartialEq + Eq + std::fmt::Debug + Clone, M: PartialOrd + Ord + std::fmt
     }
   }
 
+  // lockなど、keybindを探すときに無視するmodifierを取り除く
+  pub fn remove_modifiers(&self, modifiers: &Modifiers<M>) -> Self {
+    Self {
+      key: self.key.clone(),
+      modifiers: self.modifiers.remove(modifiers),
+    }
+  }
+
   pub fn key(&self) -> Key<K> {
     self.key.clone()
   }
@@ -114,6 +122,17 @@ impl<M: PartialOrd + Ord + Clone> Modifiers<M> {
     }
   }
 
+  // targetに含まれるものがなくても、あるものだけ取り除く
+  pub fn remove(&self, target: &Self) -> Self {
+    Self {
+      value: &self.value - &target.value,
+    }
+  }
+
+  pub fn is_subset(&self, target: &Self) -> bool {
+    self.value.is_subset(&target.value)
+  }
+
   pub fn is_empty(&self) -> bool {
     self.value.is_empty()
   }
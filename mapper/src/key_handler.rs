@@ -8,3 +8,16 @@ pub trait IsKeyHandler<
   fn press_key(&self, key_input: KeyInput<K, M>);
   fn release_key(&self, key_input: KeyInput<K, M>);
 }
+
+// 起動時に選んだkey handlerをStateに渡せるようにする
+impl<K: PartialEq + Eq + Clone + std::fmt::Debug, M: PartialOrd + Ord + Clone + std::fmt::Debug>
+  IsKeyHandler<K, M> for Box<dyn IsKeyHandler<K, M>>
+{
+  fn press_key(&self, key_input: KeyInput<K, M>) {
+    self.as_ref().press_key(key_input)
+  }
+
+  fn release_key(&self, key_input: KeyInput<K, M>) {
+    self.as_ref().release_key(key_input)
+  }
+}
@@ -0,0 +1,49 @@
+use crate::Action;
+use crate::KeyBind;
+use crate::KeyInput;
+use crate::Trigger;
+
+// keyinputが押されたときに決まったActionを返すkeybind
+pub struct ActionKeyBind<
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+  C: std::fmt::Debug + Clone,
+> {
+  from: KeyInput<K, M>,
+  action: Action<K, M, C>,
+}
+
+impl<
+    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+    C: std::fmt::Debug + Clone,
+  > ActionKeyBind<K, M, C>
+{
+  pub fn new(from: KeyInput<K, M>, action: Action<K, M, C>) -> Self {
+    log::debug!("ActionKeyBind initialized: {:?} -> {:?}", from, action);
+    Self { from, action }
+  }
+}
+
+impl<
+    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+    C: std::fmt::Debug + Clone,
+  > KeyBind<K, M, C> for ActionKeyBind<K, M, C>
+{
+  fn pressed(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
+    if *key_input == self.from {
+      Some(self.action.clone())
+    } else {
+      None
+    }
+  }
+
+  fn released(&self, _key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
+    None
+  }
+
+  fn triggers(&self) -> Vec<Trigger<K, M>> {
+    vec![Trigger::exact(self.from.clone())]
+  }
+}
@@ -0,0 +1,106 @@
+use crate::Action;
+use crate::KeyBind;
+use crate::KeyInput;
+use crate::Trigger;
+use std::time::Duration;
+
+// j+k のように、keysがtimeout以内にすべて押されたときだけ有効になるkeybind
+pub struct ChordKeyBind<
+  'a,
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+  C: std::fmt::Debug + Clone,
+> {
+  keys: Vec<KeyInput<K, M>>,
+  keybind: Box<dyn KeyBind<K, M, C> + 'a>,
+  timeout: Duration,
+}
+
+impl<
+    'a,
+    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+    C: std::fmt::Debug + Clone,
+  > ChordKeyBind<'a, K, M, C>
+{
+  // keybindには最後のkeyが押されたものとして問い合わせる
+  pub fn new(
+    keys: Vec<KeyInput<K, M>>,
+    keybind: Box<dyn KeyBind<K, M, C> + 'a>,
+    timeout: Duration,
+  ) -> Self {
+    log::debug!("ChordKeyBind initialized: {:?}", keys);
+    Self {
+      keys,
+      keybind,
+      timeout,
+    }
+  }
+
+  // 押された順番は問わない
+  fn is_part_of_chord(&self, strokes: &[KeyInput<K, M>], key_input: &KeyInput<K, M>) -> bool {
+    self.keys.contains(key_input)
+      && !strokes.contains(key_input)
+      && strokes
+        .iter()
+        .enumerate()
+        .all(|(index, stroke)| self.keys.contains(stroke) && !strokes[..index].contains(stroke))
+  }
+
+  fn is_completed_by(&self, strokes: &[KeyInput<K, M>], key_input: &KeyInput<K, M>) -> bool {
+    strokes.len() + 1 == self.keys.len() && self.is_part_of_chord(strokes, key_input)
+  }
+}
+
+impl<
+    'a,
+    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+    C: std::fmt::Debug + Clone,
+  > KeyBind<K, M, C> for ChordKeyBind<'a, K, M, C>
+{
+  fn pressed(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
+    self.pressed_in_sequence(&[], key_input)
+  }
+
+  fn released(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
+    self.released_in_sequence(&[], key_input)
+  }
+
+  fn triggers(&self) -> Vec<Trigger<K, M>> {
+    self
+      .keybind
+      .triggers()
+      .into_iter()
+      .map(|trigger| trigger.with_chord(self.keys.clone()))
+      .collect()
+  }
+
+  fn pressed_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if self.is_completed_by(strokes, key_input) {
+      self.keybind.pressed(self.keys.last()?)
+    } else if self.is_part_of_chord(strokes, key_input) {
+      Some(Action::Chord {
+        timeout: self.timeout,
+      })
+    } else {
+      None
+    }
+  }
+
+  fn released_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if self.is_completed_by(strokes, key_input) {
+      self.keybind.released(self.keys.last()?)
+    } else {
+      None
+    }
+  }
+}
@@ -1,56 +1,173 @@
 use crate::Action;
+use crate::Key;
 use crate::KeyBind;
 use crate::KeyInput;
+use crate::Matching;
+use crate::Trigger;
+use std::collections::BTreeMap;
 
 pub struct CompositKeyBind<
   'a,
-  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
   M: PartialOrd + Ord + std::fmt::Debug + Clone,
   C: std::fmt::Debug + Clone,
 > {
   keybindings: Vec<Box<dyn KeyBind<K, M, C> + 'a>>,
+  triggers: Vec<Trigger<K, M>>,
+  // keyごとに、そのkeyに反応するkeybindを具体的なものから順に並べる
+  index: BTreeMap<Key<K>, Vec<Entry<K, M>>>,
+  // triggersを返さないkeybind。どのkeyでも問い合わせる
+  unindexed: Vec<usize>,
+}
+
+// 入力済みのstrokesと、最後に押すkeyinput
+type LastStroke<K, M> = (Vec<KeyInput<K, M>>, KeyInput<K, M>);
+
+// keybindがkeyに反応する条件
+struct Entry<
+  K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+> {
+  specificity: usize,
+  keybinding: usize,
+  // 最後のstrokeとしてのkeyなら、入力済みのstrokesとmatchするkeyinput
+  // prefixやchordの途中のkeyならNone
+  last_stroke: Option<LastStroke<K, M>>,
+}
+
+impl<
+    K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+  > Entry<K, M>
+{
+  fn applies(&self, strokes: &[KeyInput<K, M>], key_input: &KeyInput<K, M>) -> bool {
+    match &self.last_stroke {
+      Some((prefix, from)) => {
+        prefix.as_slice() == strokes && !matches!(from.match_to(key_input), Matching::Unmatched)
+      }
+      None => true,
+    }
+  }
 }
 
 impl<
     'a,
-    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
     M: PartialOrd + Ord + std::fmt::Debug + Clone,
     C: std::fmt::Debug + Clone,
   > CompositKeyBind<'a, K, M, C>
 {
   pub fn new(keybindings: Vec<Box<dyn KeyBind<K, M, C> + 'a>>) -> Self {
+    let mut triggers = vec![];
+    let mut index: BTreeMap<Key<K>, Vec<Entry<K, M>>> = BTreeMap::new();
+    let mut unindexed = vec![];
+    for (keybinding, keybind) in keybindings.iter().enumerate() {
+      let keybind_triggers = keybind.triggers();
+      if keybind_triggers.is_empty() {
+        unindexed.push(keybinding);
+      }
+      for trigger in keybind_triggers.iter() {
+        for key_input in trigger.keys.iter() {
+          index.entry(key_input.key()).or_default().push(Entry {
+            specificity: trigger.modifier_count(),
+            keybinding,
+            last_stroke: Some((trigger.prefix.clone(), key_input.clone())),
+          });
+        }
+        // prefixやchordの途中のkeyにも反応する。具体性はそのkeyのmodifierの数で決める
+        let partial_keys = if trigger.keys.len() > 1 {
+          trigger.prefix.iter().chain(trigger.keys.iter()).collect()
+        } else {
+          trigger.prefix.iter().collect::<Vec<_>>()
+        };
+        for key_input in partial_keys {
+          index.entry(key_input.key()).or_default().push(Entry {
+            specificity: key_input.modifiers().to_vec().len(),
+            keybinding,
+            last_stroke: None,
+          });
+        }
+      }
+      triggers.extend(keybind_triggers);
+    }
+    for entries in index.values_mut() {
+      entries.sort_by_key(|entry| (std::cmp::Reverse(entry.specificity), entry.keybinding));
+    }
     Self {
-      keybindings: keybindings,
+      keybindings,
+      triggers,
+      index,
+      unindexed,
+    }
+  }
+
+  // matchしたkeybindのうち、modifierを多く指定したものを使う。同じなら先にあるもの
+  // 押されたkeyのentryだけを具体的なものから見るので、keybindの数によらず早く見つかる
+  fn most_specific(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+    find: impl Fn(&dyn KeyBind<K, M, C>) -> Option<Action<K, M, C>>,
+  ) -> Option<Action<K, M, C>> {
+    let find_at = |keybinding: &usize| find(self.keybindings[*keybinding].as_ref());
+    let indexed = self.index.get(&key_input.key()).and_then(|entries| {
+      entries
+        .iter()
+        .filter(|entry| entry.applies(strokes, key_input))
+        .find_map(|entry| {
+          find_at(&entry.keybinding).map(|action| (entry.specificity, entry.keybinding, action))
+        })
+    });
+    // triggersを返さないkeybindは、modifierを指定していないものとして扱う
+    match indexed {
+      Some((specificity, _, action)) if specificity > 0 => Some(action),
+      Some((_, keybinding, action)) => self
+        .unindexed
+        .iter()
+        .take_while(|unindexed| **unindexed < keybinding)
+        .find_map(find_at)
+        .or(Some(action)),
+      None => self.unindexed.iter().find_map(find_at),
     }
   }
 }
 
 impl<
     'a,
-    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
     M: PartialOrd + Ord + std::fmt::Debug + Clone,
     C: std::fmt::Debug + Clone,
   > KeyBind<K, M, C> for CompositKeyBind<'a, K, M, C>
 {
   fn pressed(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
-    for keybinding in self.keybindings.iter() {
-      if let Some(action) = keybinding.pressed(&key_input) {
-        // 一つでもbindに成功したら終了
-        return Some(action);
-      }
-    }
-
-    return None;
+    self.pressed_in_sequence(&[], key_input)
   }
 
   fn released(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
-    for keybinding in self.keybindings.iter() {
-      if let Some(action) = keybinding.released(&key_input) {
-        // 一つでもbindに成功したら終了
-        return Some(action);
-      }
-    }
+    self.released_in_sequence(&[], key_input)
+  }
+
+  fn triggers(&self) -> Vec<Trigger<K, M>> {
+    self.triggers.clone()
+  }
+
+  fn pressed_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.most_specific(strokes, key_input, |keybinding| {
+      keybinding.pressed_in_sequence(strokes, key_input)
+    })
+  }
 
-    return None;
+  fn released_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.most_specific(strokes, key_input, |keybinding| {
+      keybinding.released_in_sequence(strokes, key_input)
+    })
   }
 }
@@ -1,8 +1,15 @@
 use super::KeyBind;
+use crate::find_conflicts;
 use crate::Action;
 use crate::Application;
+use crate::ApplicationMatcher;
+use crate::Conflict;
 use crate::Focus;
 use crate::KeyInput;
+use crate::Layer;
+use crate::LayerTriggers;
+use crate::Mode;
+use crate::Precedence;
 use std::collections::BTreeMap;
 
 pub trait IsKeyBindForFocus<
@@ -14,6 +21,54 @@ pub trait IsKeyBindForFocus<
 {
   fn pressed(&self, focus: Focus<A>, key_input: KeyInput<K, M>) -> Option<Action<K, M, C>>;
   fn released(&self, focus: Focus<A>, key_input: KeyInput<K, M>) -> Option<Action<K, M, C>>;
+
+  // strokesを入力済みの状態でkey_inputが押された
+  fn pressed_in_sequence(
+    &self,
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if strokes.is_empty() {
+      self.pressed(focus, key_input)
+    } else {
+      None
+    }
+  }
+
+  fn released_in_sequence(
+    &self,
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if strokes.is_empty() {
+      self.released(focus, key_input)
+    } else {
+      None
+    }
+  }
+
+  // 有効になっているmodeのkeybindをfocusのkeybindより優先する
+  fn pressed_in_modes(
+    &self,
+    _modes: &[Mode],
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.pressed_in_sequence(focus, strokes, key_input)
+  }
+
+  fn released_in_modes(
+    &self,
+    _modes: &[Mode],
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.released_in_sequence(focus, strokes, key_input)
+  }
 }
 
 pub struct KeyBindForFocus<
@@ -23,8 +78,8 @@ pub struct KeyBindForFocus<
   M: PartialOrd + Ord + std::fmt::Debug + Clone,
   C: std::fmt::Debug + Clone,
 > {
-  global_keybind: Box<dyn KeyBind<K, M, C> + 'a>,
-  keybind_by_application: BTreeMap<Application<A>, Box<dyn KeyBind<K, M, C> + 'a>>,
+  keybind_by_layer: BTreeMap<Layer<A>, Box<dyn KeyBind<K, M, C> + 'a>>,
+  precedence: Precedence<A>,
 }
 
 impl<
@@ -39,10 +94,70 @@ impl<
     global_keybind: Box<dyn KeyBind<K, M, C> + 'a>,
     keybind_by_application: BTreeMap<Application<A>, Box<dyn KeyBind<K, M, C> + 'a>>,
   ) -> Self {
+    let mut keybind_by_layer: BTreeMap<Layer<A>, Box<dyn KeyBind<K, M, C> + 'a>> =
+      keybind_by_application
+        .into_iter()
+        .map(|(application, keybind)| (Layer::Application(application), keybind))
+        .collect();
+    keybind_by_layer.insert(Layer::Global, global_keybind);
     Self {
-      global_keybind: global_keybind,
-      keybind_by_application: keybind_by_application,
+      keybind_by_layer,
+      precedence: Precedence::new(),
+    }
+  }
+
+  pub fn with_modes(
+    mut self,
+    keybind_by_mode: BTreeMap<Mode, Box<dyn KeyBind<K, M, C> + 'a>>,
+  ) -> Self {
+    for (mode, keybind) in keybind_by_mode {
+      self.keybind_by_layer.insert(Layer::Mode(mode), keybind);
+    }
+    self
+  }
+
+  pub fn with_matchers(
+    mut self,
+    keybind_by_matcher: BTreeMap<ApplicationMatcher<A>, Box<dyn KeyBind<K, M, C> + 'a>>,
+  ) -> Self {
+    for (matcher, keybind) in keybind_by_matcher {
+      self
+        .keybind_by_layer
+        .insert(Layer::Matcher(matcher), keybind);
     }
+    self
+  }
+
+  pub fn with_precedence(mut self, precedence: Precedence<A>) -> Self {
+    self.precedence = precedence;
+    self
+  }
+
+  // 重複していたり、ほかのkeybindに隠れて使われないkeybindを探す
+  pub fn conflicts(&self) -> Vec<Conflict<A, K, M>> {
+    let triggers_by_layer: Vec<LayerTriggers<A, K, M>> = self
+      .keybind_by_layer
+      .iter()
+      .map(|(layer, keybind)| (layer.clone(), keybind.triggers()))
+      .collect();
+    find_conflicts(&triggers_by_layer, &self.precedence)
+  }
+
+  // 優先するlayerから順に探し、最初に見つかったactionを返す
+  // Disabledが見つかったら、それより優先度の低いlayerは見ない
+  fn find_action(
+    &self,
+    modes: &[Mode],
+    focus: Focus<A>,
+    find: impl Fn(&dyn KeyBind<K, M, C>) -> Option<Action<K, M, C>>,
+  ) -> Option<Action<K, M, C>> {
+    self
+      .precedence
+      .layers(modes, focus, self.keybind_by_layer.keys())
+      .iter()
+      .filter_map(|layer| self.keybind_by_layer.get(layer))
+      .find_map(|keybind| find(keybind.as_ref()))
+      .filter(|action| !matches!(action, Action::Disabled))
   }
 }
 
@@ -55,30 +170,52 @@ impl<
   > IsKeyBindForFocus<A, K, M, C> for KeyBindForFocus<'a, A, K, M, C>
 {
   fn pressed(&self, focus: Focus<A>, key_input: KeyInput<K, M>) -> Option<Action<K, M, C>> {
-    match focus {
-      Focus::NoFocus => self.global_keybind.pressed(&key_input),
-      Focus::Focused { application: app } => {
-        // まずapplicationのkeybindを見に行き、該当しなければglobalのkeybindを見る
-        self
-          .keybind_by_application
-          .get(&app)
-          .and_then(|keybind| keybind.pressed(&key_input))
-          .or(self.global_keybind.pressed(&key_input))
-      }
-    }
+    self.pressed_in_sequence(focus, &[], key_input)
   }
 
   fn released(&self, focus: Focus<A>, key_input: KeyInput<K, M>) -> Option<Action<K, M, C>> {
-    match focus {
-      Focus::NoFocus => self.global_keybind.released(&key_input),
-      Focus::Focused { application: app } => {
-        // まずapplicationのkeybindを見に行き、該当しなければglobalのkeybindを見る
-        self
-          .keybind_by_application
-          .get(&app)
-          .and_then(|keybind| keybind.released(&key_input))
-          .or(self.global_keybind.released(&key_input))
-      }
-    }
+    self.released_in_sequence(focus, &[], key_input)
+  }
+
+  fn pressed_in_sequence(
+    &self,
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.pressed_in_modes(&[], focus, strokes, key_input)
+  }
+
+  fn released_in_sequence(
+    &self,
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.released_in_modes(&[], focus, strokes, key_input)
+  }
+
+  fn pressed_in_modes(
+    &self,
+    modes: &[Mode],
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.find_action(modes, focus, |keybind| {
+      keybind.pressed_in_sequence(strokes, &key_input)
+    })
+  }
+
+  fn released_in_modes(
+    &self,
+    modes: &[Mode],
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    self.find_action(modes, focus, |keybind| {
+      keybind.released_in_sequence(strokes, &key_input)
+    })
   }
 }
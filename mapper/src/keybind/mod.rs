@@ -1,11 +1,19 @@
 use super::Action;
 use super::KeyInput;
 
+pub mod action_keybind;
+pub mod chord_keybind;
 pub mod composit_keybind;
 pub mod keybind_for_focus;
+pub mod sequence_keybind;
+pub mod trigger;
 
+pub use action_keybind::*;
+pub use chord_keybind::*;
 pub use composit_keybind::*;
 pub use keybind_for_focus::*;
+pub use sequence_keybind::*;
+pub use trigger::*;
 
 // keyinputにActionを関連付けるもの
 pub trait KeyBind<
@@ -16,4 +24,34 @@ pub trait KeyBind<
 {
   fn pressed(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>>;
   fn released(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>>;
+
+  // 反応する入力を、優先されるものから順に返す
+  fn triggers(&self) -> Vec<Trigger<K, M>> {
+    vec![]
+  }
+
+  // strokesを入力済みの状態でkey_inputが押された
+  fn pressed_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if strokes.is_empty() {
+      self.pressed(key_input)
+    } else {
+      None
+    }
+  }
+
+  fn released_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if strokes.is_empty() {
+      self.released(key_input)
+    } else {
+      None
+    }
+  }
 }
@@ -0,0 +1,84 @@
+use crate::Action;
+use crate::KeyBind;
+use crate::KeyInput;
+use crate::Trigger;
+
+// C-x C-f のように、prefixのstrokeを順番に入力したあとでだけ有効になるkeybind
+pub struct SequenceKeyBind<
+  'a,
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+  C: std::fmt::Debug + Clone,
+> {
+  prefix: Vec<KeyInput<K, M>>,
+  keybind: Box<dyn KeyBind<K, M, C> + 'a>,
+}
+
+impl<
+    'a,
+    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+    C: std::fmt::Debug + Clone,
+  > SequenceKeyBind<'a, K, M, C>
+{
+  pub fn new(prefix: Vec<KeyInput<K, M>>, keybind: Box<dyn KeyBind<K, M, C> + 'a>) -> Self {
+    log::debug!("SequenceKeyBind initialized: {:?}", prefix);
+    Self { prefix, keybind }
+  }
+}
+
+impl<
+    'a,
+    K: PartialEq + Eq + std::fmt::Debug + Clone,
+    M: PartialOrd + Ord + std::fmt::Debug + Clone,
+    C: std::fmt::Debug + Clone,
+  > KeyBind<K, M, C> for SequenceKeyBind<'a, K, M, C>
+{
+  fn pressed(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
+    self.pressed_in_sequence(&[], key_input)
+  }
+
+  fn released(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
+    self.released_in_sequence(&[], key_input)
+  }
+
+  fn triggers(&self) -> Vec<Trigger<K, M>> {
+    self
+      .keybind
+      .triggers()
+      .into_iter()
+      .map(|trigger| trigger.with_prefix(&self.prefix))
+      .collect()
+  }
+
+  fn pressed_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if strokes.starts_with(&self.prefix) {
+      // prefixを入力し終わっているので、残りのstrokeを内側のkeybindに渡す
+      self
+        .keybind
+        .pressed_in_sequence(&strokes[self.prefix.len()..], key_input)
+    } else if self.prefix.starts_with(strokes) && self.prefix[strokes.len()] == *key_input {
+      Some(Action::Prefix)
+    } else {
+      None
+    }
+  }
+
+  fn released_in_sequence(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    if strokes.starts_with(&self.prefix) {
+      self
+        .keybind
+        .released_in_sequence(&strokes[self.prefix.len()..], key_input)
+    } else {
+      None
+    }
+  }
+}
@@ -0,0 +1,72 @@
+use crate::KeyInput;
+use crate::Matching;
+
+// keybindが反応する入力。衝突の検出に使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trigger<
+  K: PartialEq + Eq + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+> {
+  // sequenceで先に入力するstroke
+  pub prefix: Vec<KeyInput<K, M>>,
+  // chordなら複数
+  pub keys: Vec<KeyInput<K, M>>,
+  // trueならkeysよりmodifierの多い入力にもmatchする
+  pub allows_extra_modifiers: bool,
+}
+
+impl<K: PartialEq + Eq + std::fmt::Debug + Clone, M: PartialOrd + Ord + std::fmt::Debug + Clone>
+  Trigger<K, M>
+{
+  pub fn exact(key_input: KeyInput<K, M>) -> Self {
+    Self {
+      prefix: vec![],
+      keys: vec![key_input],
+      allows_extra_modifiers: false,
+    }
+  }
+
+  pub fn superset(key_input: KeyInput<K, M>) -> Self {
+    Self {
+      allows_extra_modifiers: true,
+      ..Self::exact(key_input)
+    }
+  }
+
+  // prefixの前にさらにstrokeを足す
+  pub fn with_prefix(mut self, prefix: &[KeyInput<K, M>]) -> Self {
+    self.prefix.splice(0..0, prefix.iter().cloned());
+    self
+  }
+
+  // chordのkeyはそのまま比較される
+  pub fn with_chord(mut self, keys: Vec<KeyInput<K, M>>) -> Self {
+    self.keys = keys;
+    self.allows_extra_modifiers = false;
+    self
+  }
+
+  // otherが反応する入力に、必ずselfも反応するか
+  pub fn covers(&self, other: &Self) -> bool {
+    if self.prefix != other.prefix || self.keys.len() != other.keys.len() {
+      return false;
+    }
+    match (self.keys.as_slice(), other.keys.as_slice()) {
+      ([key], [other_key]) if self.allows_extra_modifiers => {
+        !matches!(key.match_to(other_key), Matching::Unmatched)
+      }
+      ([key], [other_key]) => !other.allows_extra_modifiers && key == other_key,
+      // chordは押す順番を問わない
+      (keys, other_keys) => other_keys.iter().all(|key| keys.contains(key)),
+    }
+  }
+
+  // keysに書かれたmodifierの数。多いほど具体的
+  pub fn modifier_count(&self) -> usize {
+    self
+      .keys
+      .iter()
+      .map(|key| key.modifiers().to_vec().len())
+      .sum()
+  }
+}
@@ -1,4 +1,4 @@
-use super::{Action, KeyBind, KeyInput, Matching};
+use super::{Action, KeyBind, KeyInput, Matching, Trigger};
 
 // keyinput -> keyinput というような対応を取るkeybind
 pub struct Keymap<
@@ -16,6 +16,19 @@ impl<K: PartialEq + Eq + std::fmt::Debug + Clone, M: PartialOrd + Ord + std::fmt
     log::debug!("Keymap initialized: {:?} -> {:?}", from, to);
     Self { from: from, to: to }
   }
+
+  // 毎回のkey eventで呼ばれるので、matchは一度だけにしてlogも出さない
+  fn remap<C: std::fmt::Debug + Clone>(
+    &self,
+    key_input: &KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    match self.from.match_to(key_input) {
+      Matching::Unmatched => None,
+      Matching::Remain(modifiers) => Some(Action::Key {
+        key_input: self.to.merge_modifiers(&modifiers),
+      }),
+    }
+  }
 }
 
 impl<
@@ -25,30 +38,14 @@ impl<
   > KeyBind<K, M, C> for Keymap<K, M>
 {
   fn pressed(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
-    log::debug!("{:?}", self.from.match_to(&key_input));
-    match self.from.match_to(&key_input) {
-      Matching::Unmatched => None,
-      Matching::Remain(modifiers) => {
-        let remapped_key_input = self.to.merge_modifiers(&modifiers);
-        log::debug!("{:?}", remapped_key_input);
-        Some(Action::Key {
-          key_input: remapped_key_input,
-        })
-      }
-    }
+    self.remap(key_input)
   }
 
   fn released(&self, key_input: &KeyInput<K, M>) -> Option<Action<K, M, C>> {
-    log::debug!("{:?}", self.from.match_to(&key_input));
-    match self.from.match_to(&key_input) {
-      Matching::Unmatched => None,
-      Matching::Remain(modifiers) => {
-        let remapped_key_input = self.to.merge_modifiers(&modifiers);
-        log::debug!("{:?}", remapped_key_input);
-        Some(Action::Key {
-          key_input: remapped_key_input,
-        })
-      }
-    }
+    self.remap(key_input)
+  }
+
+  fn triggers(&self) -> Vec<Trigger<K, M>> {
+    vec![Trigger::superset(self.from.clone())]
   }
 }
@@ -1,27 +1,47 @@
 pub mod action;
 pub mod application;
+pub mod application_matcher;
+pub mod application_pattern;
+pub mod chord;
+pub mod clock;
 pub mod config;
+pub mod conflict;
 pub mod event;
 pub mod event_source;
 pub mod focus;
+pub mod grab;
 pub mod key;
 pub mod key_handler;
 pub mod keybind;
 pub mod keymap;
 pub mod mock;
+pub mod mode;
 pub mod possible_keyinput_finder;
+pub mod precedence;
+pub mod reloader;
 pub mod shell_command_executor;
 pub mod state;
+pub mod tap_hold;
 
 pub use action::*;
 pub use application::*;
+pub use application_matcher::*;
+pub use application_pattern::*;
+pub use chord::*;
+pub use clock::*;
+pub use conflict::*;
 pub use event::*;
 pub use event_source::*;
 pub use focus::*;
+pub use grab::*;
 pub use key::*;
 pub use key_handler::*;
 pub use keybind::*;
 pub use keymap::*;
+pub use mode::*;
 pub use possible_keyinput_finder::*;
+pub use precedence::*;
+pub use reloader::*;
 pub use shell_command_executor::*;
 pub use state::*;
+pub use tap_hold::*;
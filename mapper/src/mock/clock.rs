@@ -0,0 +1,41 @@
+use crate::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// 生成時点からの経過時間を外から進められるclock
+#[derive(Clone)]
+pub struct MockClock {
+  base: Instant,
+  pub elapsed: Rc<Cell<Duration>>,
+}
+
+impl MockClock {
+  pub fn new() -> Self {
+    Self {
+      base: Instant::now(),
+      elapsed: Rc::new(Cell::new(Duration::from_millis(0))),
+    }
+  }
+
+  pub fn at(&self, instant: Instant) -> Duration {
+    instant.duration_since(self.base)
+  }
+}
+
+impl Default for MockClock {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl IsClock for MockClock {
+  fn now(&self) -> Instant {
+    self.base + self.elapsed.get()
+  }
+
+  // 実際には待たずに時刻だけ進める
+  fn sleep(&self, duration: Duration) {
+    self.elapsed.set(self.elapsed.get() + duration);
+  }
+}
@@ -1,12 +1,21 @@
+use super::MockClock;
 use crate::*;
 use std::rc::Rc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// grab_keysやungrab_keysが呼ばれるたびに、渡されたgrabを記録する
+pub type GrabbedKeys = Rc<Mutex<Vec<Vec<Grab<String, String>>>>>;
 
 #[derive(Clone)]
 pub struct MockEventSource {
   pub event_sequence: Rc<Mutex<Vec<Event<String, String, String>>>>,
-  pub ungrabbed_count: Rc<Mutex<usize>>,
-  pub grabbed_keys: Rc<Mutex<Vec<Vec<KeyInput<String, String>>>>>,
+  pub ungrabbed_keys: GrabbedKeys,
+  pub grabbed_keys: GrabbedKeys,
+  pub keyboard_grabbed: Rc<Mutex<bool>>,
+  // eventが起きる時刻(clockの起点からの経過時間)。clockがあるときだけ使う
+  timestamps: Rc<Mutex<Vec<Duration>>>,
+  clock: Option<MockClock>,
 }
 
 impl MockEventSource {
@@ -14,23 +23,64 @@ impl MockEventSource {
     event_sequence.reverse();
     Self {
       event_sequence: Rc::new(Mutex::new(event_sequence)),
-      ungrabbed_count: Rc::new(Mutex::new(0)),
+      ungrabbed_keys: Rc::new(Mutex::new(vec![])),
       grabbed_keys: Rc::new(Mutex::new(vec![])),
+      keyboard_grabbed: Rc::new(Mutex::new(false)),
+      timestamps: Rc::new(Mutex::new(vec![])),
+      clock: None,
     }
   }
+
+  // (ミリ秒, event) の列を受け取り、eventを返すたびにclockをその時刻まで進める
+  pub fn with_clock(
+    timed_events: Vec<(u64, Event<String, String, String>)>,
+    clock: MockClock,
+  ) -> Self {
+    let (mut timestamps, events): (Vec<Duration>, Vec<_>) = timed_events
+      .into_iter()
+      .map(|(millis, event)| (Duration::from_millis(millis), event))
+      .unzip();
+    timestamps.reverse();
+    let mut event_source = Self::new(events);
+    event_source.timestamps = Rc::new(Mutex::new(timestamps));
+    event_source.clock = Some(clock);
+    event_source
+  }
 }
 
 impl IsEventSource<String, String, String> for MockEventSource {
-  fn ungrab_keys(&self) {
-    let mut count = self.ungrabbed_count.lock().unwrap();
-    *count += 1;
+  fn ungrab_keys(&self, grabs: Vec<Grab<String, String>>) {
+    self.ungrabbed_keys.lock().unwrap().push(grabs)
   }
 
-  fn grab_keys(&self, key_inputs: Vec<KeyInput<String, String>>) {
-    self.grabbed_keys.lock().unwrap().push(key_inputs)
+  fn grab_keys(&self, grabs: Vec<Grab<String, String>>) {
+    self.grabbed_keys.lock().unwrap().push(grabs)
   }
 
   fn next(&self) -> Option<Event<String, String, String>> {
+    if let (Some(clock), Some(timestamp)) = (&self.clock, self.timestamps.lock().unwrap().pop()) {
+      clock.elapsed.set(timestamp);
+    }
     self.event_sequence.lock().unwrap().pop()
   }
+
+  fn next_until(&self, deadline: Instant) -> Option<Event<String, String, String>> {
+    if let Some(clock) = &self.clock {
+      let deadline = clock.at(deadline);
+      let next_timestamp = self.timestamps.lock().unwrap().last().cloned();
+      if !matches!(next_timestamp, Some(timestamp) if timestamp <= deadline) {
+        clock.elapsed.set(deadline);
+        return Some(Event::Tick);
+      }
+    }
+    self.next()
+  }
+
+  fn grab_keyboard(&self) {
+    *self.keyboard_grabbed.lock().unwrap() = true;
+  }
+
+  fn ungrab_keyboard(&self) {
+    *self.keyboard_grabbed.lock().unwrap() = false;
+  }
 }
@@ -10,6 +10,14 @@ impl<'a> IsIntoDomain<'a, String, String, String, String> for StringIntoDomain {
     Ok(crate::Application::new(app.0))
   }
 
+  fn into_domain_application_pattern(
+    &self,
+    app: crate::config::Application,
+  ) -> Result<crate::ApplicationPattern<String>, crate::config::InvalidConfigError> {
+    crate::ApplicationPattern::parse(app.0.clone())
+      .map_err(|_| crate::config::InvalidConfigError::InvalidApplicationPattern(app.0))
+  }
+
   fn into_domain_keyinput(
     &self,
     key_input: crate::config::KeyInput,
@@ -31,4 +31,12 @@ impl crate::KeyBind<String, String, String> for MockKeyBind {
       None
     }
   }
+
+  // modifierに関係なくkeyだけを見る
+  fn triggers(&self) -> Vec<crate::Trigger<String, String>> {
+    vec![crate::Trigger::superset(crate::KeyInput::of(
+      self.from.clone(),
+      vec![],
+    ))]
+  }
 }
@@ -1,13 +1,17 @@
+mod clock;
 mod event_source;
 mod into_domain;
 mod key_handler;
 mod keybind;
 mod keybind_for_focus;
+mod reloader;
 mod shell_command_executor;
 
+pub use clock::*;
 pub use event_source::*;
 pub use into_domain::*;
 pub use key_handler::*;
 pub use keybind::*;
 pub use keybind_for_focus::*;
+pub use reloader::*;
 pub use shell_command_executor::*;
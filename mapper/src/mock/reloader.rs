@@ -0,0 +1,46 @@
+use crate::*;
+use std::sync::Mutex;
+
+type MockReloaded<KBFF> = Result<Reloaded<String, String, String, KBFF>, String>;
+
+// 渡された結果を順に返すreloader
+pub struct MockReloader<KBFF> {
+  pub results: Mutex<Vec<MockReloaded<KBFF>>>,
+  // rebuildで返す結果
+  pub rebuilt: Mutex<Vec<MockReloaded<KBFF>>>,
+}
+
+impl<KBFF> MockReloader<KBFF> {
+  pub fn new(mut results: Vec<MockReloaded<KBFF>>) -> Self {
+    results.reverse();
+    Self {
+      results: Mutex::new(results),
+      rebuilt: Mutex::new(vec![]),
+    }
+  }
+
+  pub fn with_rebuilt(mut self, mut rebuilt: Vec<MockReloaded<KBFF>>) -> Self {
+    rebuilt.reverse();
+    self.rebuilt = Mutex::new(rebuilt);
+    self
+  }
+}
+
+fn pop<KBFF>(
+  results: &Mutex<Vec<MockReloaded<KBFF>>>,
+) -> Result<Reloaded<String, String, String, KBFF>, Box<dyn std::error::Error>> {
+  match results.lock().unwrap().pop() {
+    Some(result) => result.map_err(|message| message.into()),
+    None => Err("no more results".into()),
+  }
+}
+
+impl<KBFF> IsReloader<String, String, String, KBFF> for MockReloader<KBFF> {
+  fn reload(&self) -> Result<Reloaded<String, String, String, KBFF>, Box<dyn std::error::Error>> {
+    pop(&self.results)
+  }
+
+  fn rebuild(&self) -> Result<Reloaded<String, String, String, KBFF>, Box<dyn std::error::Error>> {
+    pop(&self.rebuilt)
+  }
+}
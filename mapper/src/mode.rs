@@ -0,0 +1,14 @@
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub struct Mode {
+  name: String,
+}
+
+impl Mode {
+  pub fn new(name: String) -> Self {
+    Self { name }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+}
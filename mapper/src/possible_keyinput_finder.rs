@@ -1,13 +1,21 @@
 use super::*;
 use std::collections::BTreeMap;
 
+// (layer, 入力済みのstrokes) -> 次に入力されうるkeyinput。strokesが空のものは1stroke目
+pub type SequenceCache<A, K, M> = BTreeMap<(Layer<A>, Vec<KeyInput<K, M>>), Vec<Grab<K, M>>>;
+
 #[derive(Debug)]
 pub struct PossibleKeyinputFinder<
   A: PartialEq + Eq + PartialOrd + Ord + Clone,
   K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
   M: PartialOrd + Ord + std::fmt::Debug + Clone,
 > {
-  cache: BTreeMap<Focus<A>, Vec<KeyInput<K, M>>>,
+  cache: SequenceCache<A, K, M>,
+  // 優先度の低いlayerでgrabしないkeyinput
+  disabled: SequenceCache<A, K, M>,
+  precedence: Precedence<A>,
+  // どのgrabでも、押されていても無視するmodifier
+  ignored_modifiers: Modifiers<M>,
 }
 
 impl<
@@ -16,30 +24,89 @@ impl<
     M: PartialOrd + Ord + std::fmt::Debug + Clone,
   > PossibleKeyinputFinder<A, K, M>
 {
-  pub fn new(cache: BTreeMap<Focus<A>, Vec<KeyInput<K, M>>>) -> Self {
-    Self { cache: cache }
-  }
-
-  // focusedであってもnofocusの結果をmergeして返す
-  // FIXME ほんとはinappがglobalを上書きするようにしたい
-  pub fn find(&self, focus: Focus<A>) -> Vec<KeyInput<K, M>> {
-    let keyinputs_for_nofocus = self.cache.get(&Focus::NoFocus).cloned().unwrap_or_default();
-    match focus {
-      Focus::Focused { application } => {
-        let mut all = vec![
-          keyinputs_for_nofocus,
-          self
-            .cache
-            .get(&Focus::Focused { application })
-            .cloned()
-            .unwrap_or_default(),
-        ]
-        .concat();
-        all.sort();
-        all.dedup();
-        all
+  pub fn new(cache: BTreeMap<Focus<A>, Vec<Grab<K, M>>>) -> Self {
+    Self::with_sequences(
+      cache
+        .into_iter()
+        .map(|(focus, keyinputs)| ((Layer::of_focus(focus), vec![]), keyinputs))
+        .collect(),
+    )
+  }
+
+  pub fn with_sequences(cache: SequenceCache<A, K, M>) -> Self {
+    Self {
+      cache,
+      disabled: BTreeMap::new(),
+      precedence: Precedence::new(),
+      ignored_modifiers: Modifiers::new(vec![]),
+    }
+  }
+
+  pub fn with_disabled(mut self, disabled: SequenceCache<A, K, M>) -> Self {
+    self.disabled = disabled;
+    self
+  }
+
+  pub fn with_precedence(mut self, precedence: Precedence<A>) -> Self {
+    self.precedence = precedence;
+    self
+  }
+
+  pub fn with_ignored_modifiers(mut self, modifiers: Modifiers<M>) -> Self {
+    self.ignored_modifiers = modifiers;
+    self
+  }
+
+  pub fn ignored_modifiers(&self) -> &Modifiers<M> {
+    &self.ignored_modifiers
+  }
+
+  // focusedのときはglobalの結果もmergeして返す
+  pub fn find(&self, focus: Focus<A>) -> Vec<Grab<K, M>> {
+    self.find_in_modes(&[], focus, &[])
+  }
+
+  // strokesを入力済みのときに、続けて入力されうるkeyinputを返す
+  pub fn find_in_sequence(&self, focus: Focus<A>, strokes: &[KeyInput<K, M>]) -> Vec<Grab<K, M>> {
+    self.find_in_modes(&[], focus, strokes)
+  }
+
+  // 有効なmodeのkeyinputもあわせて返す
+  // 優先するlayerで無効にされたkeyinputは、それより優先度の低いlayerからは返さない
+  pub fn find_in_modes(
+    &self,
+    modes: &[Mode],
+    focus: Focus<A>,
+    strokes: &[KeyInput<K, M>],
+  ) -> Vec<Grab<K, M>> {
+    let layers = self
+      .cache
+      .keys()
+      .chain(self.disabled.keys())
+      .filter(|(_, registered)| registered.as_slice() == strokes)
+      .map(|(layer, _)| layer);
+    let mut all = vec![];
+    let mut disabled = vec![];
+    for layer in self.precedence.layers(modes, focus, layers) {
+      let key = (layer, strokes.to_vec());
+      if let Some(grabs) = self.cache.get(&key) {
+        all.extend(
+          grabs
+            .iter()
+            .filter(|grab| !disabled.contains(&grab.key_input))
+            .map(|grab| {
+              grab
+                .clone()
+                .with_ignored_modifiers(self.ignored_modifiers.clone())
+            }),
+        );
+      }
+      if let Some(grabs) = self.disabled.get(&key) {
+        disabled.extend(grabs.iter().map(|grab| grab.key_input.clone()));
       }
-      Focus::NoFocus => keyinputs_for_nofocus,
     }
+    all.sort();
+    all.dedup();
+    all
   }
 }
@@ -0,0 +1,101 @@
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+// keybindを登録する単位
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub enum Layer<A: PartialEq + Eq + PartialOrd + Ord + Clone> {
+  Mode(Mode),
+  Application(Application<A>),
+  Matcher(ApplicationMatcher<A>),
+  Global,
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> Layer<A> {
+  pub fn of_focus(focus: Focus<A>) -> Self {
+    match focus {
+      Focus::Focused { application } => {
+        Layer::Application(Application::new(application.identifier().clone()))
+      }
+      Focus::NoFocus => Layer::Global,
+    }
+  }
+
+  // 有効なmodeとfocusのもとでこのlayerのkeybindを使うか
+  pub fn is_active(&self, modes: &[Mode], focus: &Focus<A>) -> bool {
+    match self {
+      Layer::Mode(mode) => modes.contains(mode),
+      Layer::Application(application) => match focus {
+        Focus::Focused {
+          application: focused,
+        } => focused.is_same_application(application),
+        Focus::NoFocus => false,
+      },
+      Layer::Matcher(matcher) => matcher.matches(focus),
+      Layer::Global => true,
+    }
+  }
+
+  // priorityが同じときの順番
+  // mode(あとから有効にしたもの優先) > titleを指定したmatcher > application > matcher(specificityの順) > global
+  fn rank(&self, modes: &[Mode]) -> (usize, usize) {
+    match self {
+      Layer::Mode(mode) => (0, modes.iter().position(|m| m == mode).unwrap_or(0)),
+      Layer::Matcher(matcher) if matcher.has_title_condition() => (1, matcher.specificity()),
+      Layer::Application(_) => (2, 0),
+      Layer::Matcher(matcher) => (3, matcher.specificity()),
+      Layer::Global => (4, 0),
+    }
+  }
+}
+
+// どのlayerのkeybindを優先するか。KeyBindForFocusとPossibleKeyinputFinderで共有する
+#[derive(Debug, Clone)]
+pub struct Precedence<A: PartialEq + Eq + PartialOrd + Ord + Clone> {
+  priorities: BTreeMap<Layer<A>, i32>,
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> Default for Precedence<A> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<A: PartialEq + Eq + PartialOrd + Ord + Clone> Precedence<A> {
+  pub fn new() -> Self {
+    Self {
+      priorities: BTreeMap::new(),
+    }
+  }
+
+  pub fn with_priority(mut self, layer: Layer<A>, priority: i32) -> Self {
+    self.priorities.insert(layer, priority);
+    self
+  }
+
+  // 指定がなければ0
+  pub fn priority(&self, layer: &Layer<A>) -> i32 {
+    self.priorities.get(layer).cloned().unwrap_or(0)
+  }
+
+  // layersのうち有効なものを、優先するものから順に返す
+  // priorityもrankも同じmatcher同士はLayerの順番で並べる
+  pub fn layers<'a>(
+    &self,
+    modes: &[Mode],
+    focus: Focus<A>,
+    layers: impl IntoIterator<Item = &'a Layer<A>>,
+  ) -> Vec<Layer<A>>
+  where
+    A: 'a,
+  {
+    let mut active: Vec<Layer<A>> = layers
+      .into_iter()
+      .filter(|layer| layer.is_active(modes, &focus))
+      .cloned()
+      .collect::<BTreeSet<_>>()
+      .into_iter()
+      .collect();
+    active.sort_by_key(|layer| (std::cmp::Reverse(self.priority(layer)), layer.rank(modes)));
+    active
+  }
+}
@@ -0,0 +1,18 @@
+use crate::*;
+
+// 読み直した設定から作ったkeybindとPossibleKeyinputFinder
+pub type Reloaded<A, K, M, KBFF> = (KBFF, PossibleKeyinputFinder<A, K, M>);
+
+// 設定を読み直して、keybindとgrabするkeyinputを作り直す
+// Event::Reloadを受け取ったときにStateから呼ばれる
+// keyboardの配列が変わったときは、fileを読まずに読み込み済みの設定から作り直す
+pub trait IsReloader<
+  A: PartialEq + Eq + PartialOrd + Ord + Clone,
+  K: PartialEq + Eq + PartialOrd + Ord + std::fmt::Debug + Clone,
+  M: PartialOrd + Ord + std::fmt::Debug + Clone,
+  KBFF,
+>
+{
+  fn reload(&self) -> Result<Reloaded<A, K, M, KBFF>, Box<dyn std::error::Error>>;
+  fn rebuild(&self) -> Result<Reloaded<A, K, M, KBFF>, Box<dyn std::error::Error>>;
+}
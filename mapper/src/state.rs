@@ -1,4 +1,9 @@
 use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+// keyboardの配列が変わったという通知は続けて届くので、この間に届いたものはまとめて1回で作り直す
+const KEYMAP_SETTLE_TIME: Duration = Duration::from_millis(100);
 
 pub trait IsState {
   fn run(&mut self);
@@ -15,11 +20,28 @@ pub struct State<
   SCE: IsShellCommandExecutor<C>,
 > {
   pub application: Option<Application<A>>,
+  // 有効なmode。あとから有効にしたものほど前にある
+  pub modes: Vec<Mode>,
   shell_command_executor: SCE,
   event_source: ES,
   key_handler: KH,
   key_bind_for_focus: KBFF,
   possible_keyinput_finder: PossibleKeyinputFinder<A, K, M>,
+  // prefixとして入力済みのstrokes
+  pending_strokes: Vec<KeyInput<K, M>>,
+  sequence_releases: BTreeMap<Key<K>, Vec<KeyInput<K, M>>>,
+  tap_hold: Option<TapHold<K, M, A>>,
+  chord: Option<PendingChord<K, M>>,
+  // 押されているkeyごとに、押したときに送った入力。離すときはmodifierが変わっていても同じものを離す
+  pressed_keys: BTreeMap<Key<K>, KeyInput<K, M>>,
+  // focusが変わったときに先に離したkey。あとで離されても何も送らない
+  released_early: BTreeSet<Key<K>>,
+  // 今grabしている入力
+  grabs: BTreeSet<Grab<K, M>>,
+  clock: Box<dyn IsClock>,
+  reloader: Option<Box<dyn IsReloader<A, K, M, KBFF>>>,
+  // keyboardの配列が変わって、設定を作り直すのを待っている期限
+  keymap_deadline: Option<Instant>,
   _c: std::marker::PhantomData<C>,
 }
 
@@ -35,45 +57,442 @@ impl<
   > State<A, K, M, C, KBFF, ES, KH, SCE>
 {
   pub fn run(&mut self) {
-    self.event_source.grab_keys(self.watch_target_key_inputs());
+    self.regrab_keys();
 
     loop {
-      match self.event_source.next() {
+      let deadline = self
+        .tap_hold
+        .as_ref()
+        .and_then(|tap_hold| tap_hold.deadline())
+        .into_iter()
+        .chain(self.chord.as_ref().map(|chord| chord.deadline))
+        .chain(self.keymap_deadline)
+        .min();
+      let event = match deadline {
+        Some(deadline) => self.event_source.next_until(deadline),
+        None => self.event_source.next(),
+      };
+      // 配列が変わったあと他のeventが来たら、待たずに作り直してから扱う
+      match event {
+        Some(Event::KeymapChanged) | None => {}
+        Some(Event::Tick) if !matches!(self.keymap_deadline, Some(deadline) if deadline <= self.clock.now()) =>
+          {}
+        _ => self.rebuild_for_keymap(),
+      }
+      match event {
+        Some(Event::ApplicationChanged { next_application })
+          if next_application == self.application => {}
         Some(Event::ApplicationChanged { next_application }) => {
+          let same_application = match (&self.application, &next_application) {
+            (Some(current), Some(next)) => current.is_same_application(next),
+            (current, next) => current.is_none() && next.is_none(),
+          };
           self.application = next_application;
-          self.event_source.ungrab_keys();
-          self.event_source.grab_keys(self.watch_target_key_inputs());
+          // titleが変わっただけなら、入力途中のsequenceや押しているkeyはそのままにする
+          if !same_application {
+            // 入力途中のsequenceは別のapplicationに持ち越さない
+            self.pending_strokes.clear();
+            self.flush_chord();
+            self.release_all();
+          }
+          self.regrab_keys();
         }
         Some(Event::KeyPressed { key_input }) => {
           log::info!("PRESS {:?}", key_input);
-          if let Some(action) = self
-            .key_bind_for_focus
-            .pressed(self.focus(), key_input.clone())
-          {
-            match action {
-              Action::Key {
-                key_input: bound_key_input,
-              } => self.key_handler.press_key(bound_key_input),
-              Action::Execution { execution } => self.shell_command_executor.execute(execution),
-            }
-          }
+          self.handle_pressed(key_input);
         }
         Some(Event::KeyReleased { key_input }) => {
           log::info!("RELEASE {:?}", key_input);
-          if let Some(action) = self
-            .key_bind_for_focus
-            .released(self.focus(), key_input.clone())
-          {
-            match action {
-              Action::Key {
-                key_input: bound_key_input,
-              } => self.key_handler.release_key(bound_key_input),
-              Action::Execution { execution } => self.shell_command_executor.execute(execution),
-            }
+          self.handle_released(key_input);
+        }
+        Some(Event::Tick) => self.tick(),
+        Some(Event::Reload) => self.reload(),
+        Some(Event::KeymapChanged) => {
+          let deadline = self.clock.now() + KEYMAP_SETTLE_TIME;
+          self.keymap_deadline.get_or_insert(deadline);
+        }
+        None => {
+          // 押したままのkeyを残して終了しない
+          self.release_all();
+          return;
+        }
+      }
+    }
+  }
+
+  fn handle_pressed(&mut self, key_input: KeyInput<K, M>) {
+    match self.tap_hold.take() {
+      Some(tap_hold) if tap_hold.key() == key_input.key() => {
+        // 押しっぱなしによるkey repeatは無視する
+        self.tap_hold = Some(tap_hold);
+      }
+      Some(TapHold::Undecided {
+        key,
+        tap,
+        hold,
+        deadline,
+        mut buffered,
+      }) => {
+        buffered.push(Event::KeyPressed { key_input });
+        self.tap_hold = Some(TapHold::Undecided {
+          key,
+          tap,
+          hold,
+          deadline,
+          buffered,
+        });
+      }
+      Some(TapHold::Held { key, hold }) => {
+        let key_input = key_input.merge_modifiers(&Modifiers::new(vec![hold.clone()]));
+        self.tap_hold = Some(TapHold::Held { key, hold });
+        self.forward_pressed(key_input);
+      }
+      None => {
+        self.forward_pressed(key_input);
+      }
+    }
+  }
+
+  fn handle_released(&mut self, key_input: KeyInput<K, M>) {
+    match self.tap_hold.take() {
+      Some(TapHold::Undecided {
+        key, tap, buffered, ..
+      }) if key == key_input.key() => {
+        // timeoutより前に離されたのでtap
+        self.event_source.ungrab_keyboard();
+        self.key_handler.press_key(tap.clone());
+        self.key_handler.release_key(tap);
+        self.replay(buffered);
+      }
+      Some(tap_hold @ TapHold::Undecided { .. }) => {
+        let nested = tap_hold.has_buffered_press(&key_input.key());
+        if let TapHold::Undecided {
+          key,
+          tap,
+          hold,
+          deadline,
+          mut buffered,
+        } = tap_hold
+        {
+          buffered.push(Event::KeyReleased { key_input });
+          if nested {
+            // 他のkeyが押されて離されたので、timeoutを待たずにholdとみなす(permissive hold)
+            self.tap_hold = Some(TapHold::Held { key, hold });
+            self.replay(buffered);
+          } else {
+            self.tap_hold = Some(TapHold::Undecided {
+              key,
+              tap,
+              hold,
+              deadline,
+              buffered,
+            });
+          }
+        }
+      }
+      Some(TapHold::Held { key, .. }) if key == key_input.key() => {
+        self.event_source.ungrab_keyboard();
+      }
+      Some(TapHold::Held { key, hold }) => {
+        let key_input = key_input.merge_modifiers(&Modifiers::new(vec![hold.clone()]));
+        self.tap_hold = Some(TapHold::Held { key, hold });
+        self.forward_released(key_input);
+      }
+      None if self.chord.is_some() => {
+        self.forward_released(key_input);
+      }
+      None => {
+        self.key_released(self.without_ignored_modifiers(&key_input));
+      }
+    }
+  }
+
+  fn tick(&mut self) {
+    if matches!(&self.chord, Some(chord) if chord.deadline <= self.clock.now()) {
+      self.flush_chord();
+    }
+    match self.tap_hold.take() {
+      Some(TapHold::Undecided {
+        key,
+        hold,
+        deadline,
+        buffered,
+        ..
+      }) if deadline <= self.clock.now() => {
+        self.tap_hold = Some(TapHold::Held { key, hold });
+        self.replay(buffered);
+      }
+      tap_hold => self.tap_hold = tap_hold,
+    }
+  }
+
+  // tap/holdの判定中に横取りしていたeventを改めて処理する
+  fn replay(&mut self, events: Vec<Event<K, M, A>>) {
+    for event in events {
+      match event {
+        Event::KeyPressed { key_input } => match self.tap_hold {
+          Some(_) => self.handle_pressed(key_input),
+          None => self.forward_pressed(key_input),
+        },
+        Event::KeyReleased { key_input } => match self.tap_hold {
+          Some(_) => self.handle_released(key_input),
+          None => self.forward_released(key_input),
+        },
+        _ => {}
+      }
+    }
+  }
+
+  // grabしたkeyにkeybindがなかったときは、握りつぶさずにそのまま送る
+  // 無視するmodifierはkeybindを探すときだけ取り除き、そのまま送るときは残す
+  fn forward_pressed(&mut self, key_input: KeyInput<K, M>) {
+    if !self.key_pressed(self.without_ignored_modifiers(&key_input)) {
+      self.press_output(key_input.key(), key_input);
+    }
+  }
+
+  fn forward_released(&mut self, key_input: KeyInput<K, M>) {
+    if !self.key_released(self.without_ignored_modifiers(&key_input)) {
+      self.key_handler.release_key(key_input);
+    }
+  }
+
+  fn without_ignored_modifiers(&self, key_input: &KeyInput<K, M>) -> KeyInput<K, M> {
+    key_input.remove_modifiers(self.possible_keyinput_finder.ignored_modifiers())
+  }
+
+  // keyboardごとgrabしている間はwithにないmodifierで押されたものも届くので、keybindを探さない
+  fn pressed_in_modes(
+    &self,
+    strokes: &[KeyInput<K, M>],
+    key_input: KeyInput<K, M>,
+  ) -> Option<Action<K, M, C>> {
+    let grabbed_with_optional_modifiers = self
+      .grabs
+      .iter()
+      .any(|grab| grab.key() == key_input.key() && !grab.optional_modifiers.is_empty());
+    if grabbed_with_optional_modifiers && !self.grabs.iter().any(|grab| grab.covers(&key_input)) {
+      return None;
+    }
+    self
+      .key_bind_for_focus
+      .pressed_in_modes(&self.modes, self.focus(), strokes, key_input)
+  }
+
+  fn key_pressed(&mut self, key_input: KeyInput<K, M>) -> bool {
+    if self.chord.is_some() {
+      return self.chord_pressed(key_input);
+    }
+    let strokes = self.pending_strokes.clone();
+    match self.pressed_in_modes(&strokes, key_input.clone()) {
+      Some(Action::Prefix) => {
+        self.pending_strokes.push(key_input);
+        self.regrab_keys();
+        true
+      }
+      Some(Action::TapHold { tap, hold, timeout }) => {
+        self.event_source.grab_keyboard();
+        self.tap_hold = Some(TapHold::Undecided {
+          key: key_input.key(),
+          tap,
+          hold,
+          deadline: self.clock.now() + timeout,
+          buffered: vec![],
+        });
+        true
+      }
+      Some(Action::Chord { timeout }) => {
+        // 判定中に押された他のkeyの順番を保つため、keyboardごと横取りする
+        if self.tap_hold.is_none() {
+          self.event_source.grab_keyboard();
+        }
+        self.chord = Some(PendingChord::new(key_input, self.clock.now() + timeout));
+        true
+      }
+      Some(action) => {
+        if !strokes.is_empty() {
+          // releaseも同じsequenceの続きとして解決する
+          self.sequence_releases.insert(key_input.key(), strokes);
+          self.pending_strokes.clear();
+          self.regrab_keys();
+        }
+        self.act_pressed(key_input.key(), action);
+        true
+      }
+      None => {
+        if strokes.is_empty() {
+          return false;
+        }
+        // sequenceが成立しなかったので、握りつぶしていたprefixを送り直してから通常のkeyとして扱う
+        self.pending_strokes.clear();
+        self.regrab_keys();
+        for stroke in strokes {
+          self.key_handler.press_key(stroke.clone());
+          self.key_handler.release_key(stroke);
+        }
+        self.key_pressed(key_input)
+      }
+    }
+  }
+
+  fn chord_pressed(&mut self, key_input: KeyInput<K, M>) -> bool {
+    let mut chord = match self.chord.take() {
+      Some(chord) => chord,
+      None => return self.key_pressed(key_input),
+    };
+    let mut strokes = self.pending_strokes.clone();
+    strokes.extend(chord.strokes.clone());
+    match self.pressed_in_modes(&strokes, key_input.clone()) {
+      Some(Action::Chord { .. }) => {
+        chord.strokes.push(key_input);
+        self.chord = Some(chord);
+        true
+      }
+      Some(action) => {
+        self.ungrab_keyboard_for_chord();
+        self.sequence_releases.insert(key_input.key(), strokes);
+        if !self.pending_strokes.is_empty() {
+          self.pending_strokes.clear();
+          self.regrab_keys();
+        }
+        self.act_pressed(key_input.key(), action);
+        true
+      }
+      None => {
+        self.chord = Some(chord);
+        self.flush_chord();
+        self.key_pressed(key_input)
+      }
+    }
+  }
+
+  // chordが成立しなかったので、貯めていたkeyを押された順にそのまま送る
+  fn flush_chord(&mut self) {
+    let chord = match self.chord.take() {
+      Some(chord) => chord,
+      None => return,
+    };
+    self.ungrab_keyboard_for_chord();
+    let strokes = std::mem::take(&mut self.pending_strokes);
+    if !strokes.is_empty() {
+      self.regrab_keys();
+    }
+    for stroke in strokes {
+      self.key_handler.press_key(stroke.clone());
+      self.key_handler.release_key(stroke);
+    }
+    for stroke in chord.strokes {
+      self.press_output(stroke.key(), stroke);
+    }
+  }
+
+  // tap/holdの判定でもkeyboardを横取りしているときはそのままにする
+  fn ungrab_keyboard_for_chord(&self) {
+    if self.tap_hold.is_none() {
+      self.event_source.ungrab_keyboard();
+    }
+  }
+
+  fn key_released(&mut self, key_input: KeyInput<K, M>) -> bool {
+    if matches!(&self.chord, Some(chord) if chord.contains_key(&key_input.key())) {
+      // 残りのkeyが押される前に離されたのでchordは成立しない
+      self.flush_chord();
+    }
+    let strokes = self
+      .sequence_releases
+      .remove(&key_input.key())
+      .unwrap_or_default();
+    if self.release_output(&key_input.key()) {
+      return true;
+    }
+    if let Some(action) =
+      self
+        .key_bind_for_focus
+        .released_in_modes(&self.modes, self.focus(), &strokes, key_input)
+    {
+      match action {
+        Action::Key {
+          key_input: bound_key_input,
+        } => self.key_handler.release_key(bound_key_input),
+        Action::Execution { execution } => self.shell_command_executor.execute(execution),
+        _ => {}
+      }
+      true
+    } else {
+      false
+    }
+  }
+
+  fn act_pressed(&mut self, key: Key<K>, action: Action<K, M, C>) {
+    match action {
+      Action::Key {
+        key_input: bound_key_input,
+      } => self.press_output(key, bound_key_input),
+      Action::Execution { execution } => self.shell_command_executor.execute(execution),
+      Action::Sequence { steps } => {
+        for step in steps {
+          if step.delay > Duration::from_millis(0) {
+            self.clock.sleep(step.delay);
           }
+          self.key_handler.press_key(step.key_input.clone());
+          self.key_handler.release_key(step.key_input);
+        }
+      }
+      Action::Prefix | Action::TapHold { .. } | Action::Chord { .. } | Action::Disabled => {}
+      Action::EnterMode { mode } => {
+        if !self.modes.contains(&mode) {
+          self.modes.insert(0, mode);
+          self.regrab_keys();
+        }
+      }
+      Action::ExitMode { mode } => {
+        if self.modes.contains(&mode) {
+          self.modes.retain(|active| *active != mode);
+          self.regrab_keys();
+        }
+      }
+      Action::ToggleMode { mode } => {
+        if self.modes.contains(&mode) {
+          self.modes.retain(|active| *active != mode);
+        } else {
+          self.modes.insert(0, mode);
         }
-        None => return,
+        self.regrab_keys();
+      }
+    }
+    log::debug!("modes: {:?}", self.modes);
+  }
+
+  // keyが押されて送った入力を覚えておく。key repeatで別の入力になったら前のものは離す
+  fn press_output(&mut self, key: Key<K>, output: KeyInput<K, M>) {
+    self.released_early.remove(&key);
+    if let Some(previous) = self.pressed_keys.insert(key, output.clone()) {
+      if previous != output {
+        self.key_handler.release_key(previous);
+      }
+    }
+    self.key_handler.press_key(output);
+  }
+
+  // keyを押したときに送った入力を離す。覚えていなければfalse
+  fn release_output(&mut self, key: &Key<K>) -> bool {
+    if self.released_early.remove(key) {
+      return true;
+    }
+    match self.pressed_keys.remove(key) {
+      Some(output) => {
+        self.key_handler.release_key(output);
+        true
       }
+      None => false,
+    }
+  }
+
+  // 押したままの入力をすべて離す
+  pub fn release_all(&mut self) {
+    for (key, output) in std::mem::take(&mut self.pressed_keys) {
+      self.key_handler.release_key(output);
+      self.released_early.insert(key);
     }
   }
 }
@@ -98,17 +517,143 @@ impl<
   ) -> Self {
     Self {
       application: None,
+      modes: vec![],
       event_source: event_source,
       key_handler: key_handler,
       key_bind_for_focus: key_bind_for_focus,
       possible_keyinput_finder: possible_keyinput_finder,
       shell_command_executor: shell_command_executor,
+      pending_strokes: vec![],
+      sequence_releases: BTreeMap::new(),
+      tap_hold: None,
+      chord: None,
+      pressed_keys: BTreeMap::new(),
+      released_early: BTreeSet::new(),
+      grabs: BTreeSet::new(),
+      clock: Box::new(SystemClock),
+      reloader: None,
+      keymap_deadline: None,
       _c: std::marker::PhantomData,
     }
   }
 
-  fn watch_target_key_inputs(&self) -> Vec<KeyInput<K, M>> {
-    let keyinputs = self.possible_keyinput_finder.find(self.focus());
+  pub fn with_clock(mut self, clock: impl IsClock + 'static) -> Self {
+    self.clock = Box::new(clock);
+    self
+  }
+
+  pub fn with_reloader(mut self, reloader: impl IsReloader<A, K, M, KBFF> + 'static) -> Self {
+    self.reloader = Some(Box::new(reloader));
+    self
+  }
+
+  // 動いているまま設定を差し替える。focusと有効なmodeはそのまま引き継ぐ
+  pub fn swap_config(
+    &mut self,
+    key_bind_for_focus: KBFF,
+    possible_keyinput_finder: PossibleKeyinputFinder<A, K, M>,
+  ) {
+    self.key_bind_for_focus = key_bind_for_focus;
+    self.possible_keyinput_finder = possible_keyinput_finder;
+    // 入力途中のsequenceは新しい設定に持ち越さない
+    self.pending_strokes.clear();
+    self.flush_chord();
+    self.regrab_keys();
+  }
+
+  // 読み直せなかったときは、今の設定のまま動き続ける
+  fn reload(&mut self) {
+    if let Some((key_bind_for_focus, possible_keyinput_finder)) = self.reload_config() {
+      self.swap_config(key_bind_for_focus, possible_keyinput_finder);
+    }
+  }
+
+  fn reload_config(&self) -> Option<Reloaded<A, K, M, KBFF>> {
+    match self.reloader.as_ref()?.reload() {
+      Ok(reloaded) => {
+        log::info!("config reloaded");
+        Some(reloaded)
+      }
+      Err(err) => {
+        log::error!("failed to reload config, keep the current one: {}", err);
+        None
+      }
+    }
+  }
+
+  // grabし直すのは変わったkeyだけにして、その間に他のkeyが漏れないようにする
+  // 同じkeyのgrabはmodifierの組み合わせが重なることがあるので、変わったkeyはそのkeyのgrabをまとめてやり直す
+  fn regrab_keys(&mut self) {
+    let grabs: BTreeSet<Grab<K, M>> = self.watch_target_key_inputs().into_iter().collect();
+    let changed_keys: BTreeSet<Key<K>> = self
+      .grabs
+      .symmetric_difference(&grabs)
+      .map(|grab| grab.key())
+      .collect();
+    let of_changed_keys = |grabs: &BTreeSet<Grab<K, M>>| -> Vec<Grab<K, M>> {
+      grabs
+        .iter()
+        .filter(|grab| changed_keys.contains(&grab.key()))
+        .cloned()
+        .collect()
+    };
+    let ungrabbed = of_changed_keys(&self.grabs);
+    let grabbed = of_changed_keys(&grabs);
+    if !ungrabbed.is_empty() {
+      self.event_source.ungrab_keys(ungrabbed);
+    }
+    if !grabbed.is_empty() {
+      self.event_source.grab_keys(grabbed);
+    }
+    self.grabs = grabs;
+  }
+
+  // keycodeが変わっているので、grabしている入力をすべてgrabし直す
+  // Shiftで出すkeysymなどはlayoutによって変換が変わるので、reloaderがあれば設定も作り直す
+  // 配列が変わるとkeycodeが変わるので、読み込み済みの設定をその配列で作り直してgrabし直す
+  // 作り直せなかったときは、今grabしている入力をgrabし直す
+  fn rebuild_for_keymap(&mut self) {
+    if self.keymap_deadline.take().is_none() {
+      return;
+    }
+    let grabs: Vec<Grab<K, M>> = std::mem::take(&mut self.grabs).into_iter().collect();
+    if !grabs.is_empty() {
+      self.event_source.ungrab_keys(grabs);
+    }
+    let rebuilt = match self.reloader.as_ref().map(|reloader| reloader.rebuild()) {
+      Some(Ok(rebuilt)) => {
+        log::info!("config rebuilt for the new keyboard layout");
+        Some(rebuilt)
+      }
+      Some(Err(err)) => {
+        log::error!(
+          "failed to rebuild config for the new keyboard layout: {}",
+          err
+        );
+        None
+      }
+      None => None,
+    };
+    match rebuilt {
+      Some((key_bind_for_focus, possible_keyinput_finder)) => {
+        self.swap_config(key_bind_for_focus, possible_keyinput_finder)
+      }
+      None => self.regrab_keys(),
+    }
+  }
+
+  // prefixが入力済みのときだけ、続くstrokeもgrabする
+  fn watch_target_key_inputs(&self) -> Vec<Grab<K, M>> {
+    let mut keyinputs = self
+      .possible_keyinput_finder
+      .find_in_modes(&self.modes, self.focus(), &[]);
+    if !self.pending_strokes.is_empty() {
+      keyinputs.extend(self.possible_keyinput_finder.find_in_modes(
+        &self.modes,
+        self.focus(),
+        &self.pending_strokes,
+      ));
+    }
     log::trace!("watch_target_key_inputs: {:?}", keyinputs);
     keyinputs
   }
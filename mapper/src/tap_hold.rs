@@ -0,0 +1,55 @@
+use crate::*;
+use std::time::Instant;
+
+// tapとholdで別の役割を持つkeyの状態
+pub enum TapHold<
+  K: PartialEq + Eq + Clone + std::fmt::Debug,
+  M: PartialOrd + Ord + Clone + std::fmt::Debug,
+  A: PartialEq + Eq + PartialOrd + Ord + Clone,
+> {
+  // tapかholdかまだ決まっていない。その間に来たeventはbufferに貯めておく
+  Undecided {
+    key: Key<K>,
+    tap: KeyInput<K, M>,
+    hold: Modifier<M>,
+    deadline: Instant,
+    buffered: Vec<Event<K, M, A>>,
+  },
+  // holdに決まった。keyが離されるまで他のkeyにholdのmodifierを足す
+  Held {
+    key: Key<K>,
+    hold: Modifier<M>,
+  },
+}
+
+impl<
+    K: PartialEq + Eq + Clone + std::fmt::Debug,
+    M: PartialOrd + Ord + Clone + std::fmt::Debug,
+    A: PartialEq + Eq + PartialOrd + Ord + Clone,
+  > TapHold<K, M, A>
+{
+  pub fn key(&self) -> Key<K> {
+    match self {
+      TapHold::Undecided { key, .. } => key.clone(),
+      TapHold::Held { key, .. } => key.clone(),
+    }
+  }
+
+  pub fn deadline(&self) -> Option<Instant> {
+    match self {
+      TapHold::Undecided { deadline, .. } => Some(*deadline),
+      TapHold::Held { .. } => None,
+    }
+  }
+
+  // 判定中にkeyが押されていたか
+  pub fn has_buffered_press(&self, target: &Key<K>) -> bool {
+    match self {
+      TapHold::Undecided { buffered, .. } => buffered.iter().any(|event| match event {
+        Event::KeyPressed { key_input } => key_input.key() == *target,
+        _ => false,
+      }),
+      TapHold::Held { .. } => false,
+    }
+  }
+}
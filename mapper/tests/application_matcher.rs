@@ -0,0 +1,118 @@
+use speculate::speculate;
+
+speculate! {
+  describe "ApplicationMatcher" {
+    before {
+      use mapper::{ApplicationMatcher, ApplicationPattern};
+
+      let pattern = |source: &str| ApplicationPattern::<String>::parse(source.to_string()).unwrap();
+    }
+
+    describe "#matches" {
+      before {
+        use mapper::{Application, Focus};
+
+        let focused = |name: &str| Focus::Focused { application: Application::new(name.to_string()) };
+      }
+
+      it "matches only listed applications with class_only" {
+        let matcher = ApplicationMatcher::only(vec![pattern("chromium"), pattern("discord")]);
+        assert!(matcher.matches(&focused("discord")));
+        assert!(!matcher.matches(&focused("Alacritty")));
+        assert!(!matcher.matches(&Focus::NoFocus));
+      }
+
+      it "matches all but listed applications with class_not" {
+        let matcher = ApplicationMatcher::not(vec![pattern("Alacritty"), pattern("Steam")]);
+        assert!(matcher.matches(&focused("discord")));
+        assert!(!matcher.matches(&focused("Steam")));
+        assert!(matcher.matches(&Focus::NoFocus));
+      }
+
+      it "combines class_only and class_not" {
+        let matcher = ApplicationMatcher::new(Some(vec![pattern("chromium"), pattern("discord")]), vec![pattern("discord")]);
+        assert!(matcher.matches(&focused("chromium")));
+        assert!(!matcher.matches(&focused("discord")));
+      }
+
+      it "matches applications with glob and regex" {
+        let glob = ApplicationMatcher::only(vec![pattern("jetbrains-*")]);
+        assert!(glob.matches(&focused("jetbrains-idea-ce")));
+        assert!(glob.matches(&focused("jetbrains-clion")));
+        assert!(!glob.matches(&focused("xjetbrains-clion")));
+
+        let regex = ApplicationMatcher::not(vec![pattern("/^[Ee]lectron/")]);
+        assert!(!regex.matches(&focused("Electron12")));
+        assert!(regex.matches(&focused("chromium")));
+      }
+
+      it "matches glob character classes with ranges and negation" {
+        let range = ApplicationMatcher::only(vec![pattern("jetbrains-[a-z]*")]);
+        assert!(range.matches(&focused("jetbrains-idea")));
+        assert!(range.matches(&focused("jetbrains-a")));
+        assert!(!range.matches(&focused("jetbrains-9")));
+        assert!(!range.matches(&focused("jetbrains--")));
+
+        let negated = ApplicationMatcher::only(vec![pattern("term[!0-9]"), pattern("shell[^a-c]")]);
+        assert!(negated.matches(&focused("termx")));
+        assert!(!negated.matches(&focused("term1")));
+        assert!(negated.matches(&focused("shelld")));
+        assert!(!negated.matches(&focused("shellb")));
+
+        let literal = ApplicationMatcher::only(vec![pattern("[]x]y"), pattern("[!]]z")]);
+        assert!(literal.matches(&focused("]y")));
+        assert!(literal.matches(&focused("xy")));
+        assert!(literal.matches(&focused("az")));
+        assert!(!literal.matches(&focused("]z")));
+      }
+
+      it "matches window titles with title_only and title_not" {
+        use mapper::TitlePattern;
+
+        let title = |source: &str| TitlePattern::parse(source.to_string()).unwrap();
+        let titled = |name: &str, title: &str| Focus::Focused {
+          application: Application::new(name.to_string()).with_title(title.to_string()),
+        };
+        let matcher = ApplicationMatcher::only(vec![pattern("chromium")])
+          .with_titles(Some(vec![title("*Gmail*")]), vec![title("/^Draft/")]);
+        assert!(matcher.matches(&titled("chromium", "Inbox - Gmail")));
+        assert!(!matcher.matches(&titled("chromium", "Draft - Gmail")));
+        assert!(!matcher.matches(&titled("chromium", "Google Docs")));
+        assert!(!matcher.matches(&titled("firefox", "Inbox - Gmail")));
+        assert!(!matcher.matches(&focused("chromium")));
+
+        let range = title("[a-c]x");
+        assert!(range.matches("bx"));
+        assert!(!range.matches("-x"));
+        assert!(!range.matches("dx"));
+      }
+    }
+
+    describe "#to_string" {
+      it "lists the conditions as they are written in the config" {
+        use mapper::TitlePattern;
+
+        let title = |source: &str| TitlePattern::parse(source.to_string()).unwrap();
+        let matcher = ApplicationMatcher::new(Some(vec![pattern("chromium"), pattern("/^chrom/")]), vec![pattern("chrom*")])
+          .with_titles(None, vec![title("/^Draft/")]);
+        assert_eq!(
+          matcher.to_string(),
+          "class_only: [chromium, /^chrom/], class_not: [chrom*], title_not: [/^Draft/]"
+        );
+        assert_eq!(ApplicationMatcher::not(vec![pattern("Alacritty")]).to_string(), "class_not: [Alacritty]");
+      }
+    }
+
+    describe "#specificity" {
+      it "prefers exact, glob, regex and class_not in this order" {
+        let specificities: Vec<usize> = [
+          ApplicationMatcher::only(vec![pattern("chromium")]),
+          ApplicationMatcher::only(vec![pattern("chrom*")]),
+          ApplicationMatcher::only(vec![pattern("chromium"), pattern("/^chrom/")]),
+          ApplicationMatcher::not(vec![pattern("chromium")]),
+        ].iter().map(|matcher| matcher.specificity()).collect();
+        assert_eq!(specificities, vec![0, 1, 2, 3]);
+      }
+    }
+  }
+}
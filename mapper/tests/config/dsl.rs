@@ -0,0 +1,128 @@
+use speculate::speculate;
+
+use mapper::config::dsl::{parse_dsl, DslError, Pos};
+use serde_json::json;
+
+speculate! {
+  describe "parse_dsl" {
+    context "when global remaps are written" {
+      before {
+        let parse = |source: &str| serde_json::to_value(parse_dsl(source).unwrap()).unwrap();
+      }
+
+      it "converts keys, modifiers, executions and sequences" {
+        let config = parse(r#"
+          remap 'C-b', to: 'Left'
+          remap "Control-h", to: 'Left', with_modifier: 'Shift' # comment
+          remap 'C-o', to: execute('rofi -show run')
+          remap 'C-t', to: ['C-a', delay('k', 100)]
+          remap 'Alt-n', to: enter_mode('nav')
+          remap 'C-a', to: disable
+        "#);
+        assert_eq!(config["remap"]["C-b"], json!({ "to": "Left", "with": [] }));
+        assert_eq!(config["remap"]["Control-h"], json!({ "to": "Left", "with": ["Shift"] }));
+        assert_eq!(config["remap"]["C-o"], json!({ "execute": "rofi -show run" }));
+        assert_eq!(config["remap"]["C-t"], json!({ "sequence": ["C-a", { "to": "k", "delay": 100 }] }));
+        assert_eq!(config["remap"]["Alt-n"], json!({ "enter_mode": "nav" }));
+        assert_eq!(config["remap"]["C-a"], json!({ "disable": true }));
+      }
+
+      it "expands %w arrays in each loops with interpolation" {
+        let config = parse(r#"
+          %w[r z x].each do |key|
+            remap "Alt-#{key}", to: "C-#{key}"
+          end
+          [['a', 'Home'], ['e', 'End']].each { |from, to| remap "C-#{from}", to: to }
+        "#);
+        assert_eq!(config["remap"]["Alt-r"], json!({ "to": "C-r", "with": [] }));
+        assert_eq!(config["remap"]["Alt-x"], json!({ "to": "C-x", "with": [] }));
+        assert_eq!(config["remap"]["C-e"], json!({ "to": "End", "with": [] }));
+      }
+    }
+
+    context "when window is written" {
+      before {
+        let parse = |source: &str| serde_json::to_value(parse_dsl(source).unwrap()).unwrap();
+      }
+
+      it "registers remaps of class_only for each application" {
+        let config = parse(r#"
+          window class_only: %w[chromium discord], priority: 2 do
+            remap 'Alt-w', to: 'C-w'
+          end
+        "#);
+        assert_eq!(config["in_app"]["chromium"]["Alt-w"], json!({ "to": "C-w", "with": [] }));
+        assert_eq!(config["in_app"]["discord"]["Alt-w"], json!({ "to": "C-w", "with": [] }));
+        assert_eq!(config["priority"]["in_app"]["discord"], json!(2));
+        assert_eq!(config["remap"], json!({}));
+      }
+
+      it "registers windows with class_not, titles and regexps" {
+        let config = parse(r#"
+          window class_not: 'Alacritty', title_only: /vim/i do
+            remap 'C-h', to: 'BackSpace'
+          end
+        "#);
+        assert_eq!(config["windows"][0]["class_not"], json!(["Alacritty"]));
+        assert_eq!(config["windows"][0]["title_only"], json!(["/(?i)vim/"]));
+        assert_eq!(config["windows"][0]["remap"]["C-h"], json!({ "to": "BackSpace", "with": [] }));
+      }
+    }
+
+    context "when modes and settings are written" {
+      before {
+        let parse = |source: &str| serde_json::to_value(parse_dsl(source).unwrap()).unwrap();
+      }
+
+      it "converts them" {
+        let config = parse(r#"
+          chord_timeout 30
+          ignore_modifiers %w[Mod3 Mod5]
+          global_priority 1
+          remap 'j+k', to: 'Escape'
+          remap 'space', to: tap_hold(tap: 'space', hold: 'Control', timeout: 150)
+          mode 'nav', priority: 3 do
+            remap('h', to: 'Left')
+          end
+        "#);
+        assert_eq!(config["chord_timeout"], json!(30));
+        assert_eq!(config["ignore_modifiers"], json!(["Mod3", "Mod5"]));
+        assert_eq!(config["priority"]["remap"], json!(1));
+        assert_eq!(config["priority"]["modes"]["nav"], json!(3));
+        assert_eq!(config["modes"]["nav"]["h"], json!({ "to": "Left", "with": [] }));
+        assert_eq!(config["remap"]["space"], json!({ "tap": "space", "hold": "Control", "hold_timeout": 150 }));
+      }
+    }
+
+    context "when README example is given" {
+      before {
+        let parse = |source: &str| serde_json::to_value(parse_dsl(source).unwrap()).unwrap();
+      }
+
+      it "parses it" {
+        let readme = include_str!("../../../README.md");
+        let example = readme.split("```ruby").nth(1).unwrap().split("```").next().unwrap();
+        let config = parse(example);
+        assert_eq!(config["in_app"]["emacs"]["C-a"], json!({ "disable": true }));
+      }
+    }
+
+    context "when source is invalid" {
+      it "reports undefined methods with position" {
+        let err = parse_dsl("remap 'a', to: 'b'\n  remapp 'c', to: 'd'\n").unwrap_err();
+        assert_eq!(err, DslError::new(Pos { line: 2, column: 3 }, "undefined method `remapp`"));
+      }
+
+      it "reports syntax errors with position" {
+        let err = parse_dsl("window class_only: 'emacs' do\n  remap 'C-a', to: 'Home'\n").unwrap_err();
+        assert_eq!(err.pos, Pos { line: 3, column: 1 });
+        assert_eq!(err.to_string(), "3:1: expected `end`, found end of file");
+      }
+
+      it "reports unknown keywords" {
+        let err = parse_dsl("remap 'a', too: 'b'").unwrap_err();
+        assert_eq!(err.to_string(), "1:1: unknown keyword for `remap`: too");
+      }
+    }
+  }
+}
@@ -0,0 +1,65 @@
+use speculate::speculate;
+
+use mapper::config::{ConfigFormat, FormatError};
+use serde_json::json;
+
+speculate! {
+  describe "config format" {
+    describe "#from_extension" {
+      it "chooses format by file extension" {
+        let format = |path: &str| ConfigFormat::from_extension(std::path::Path::new(path));
+        assert_eq!(format("~/.rumap.json"), Some(ConfigFormat::Json));
+        assert_eq!(format("~/.config/rumap.toml"), Some(ConfigFormat::Toml));
+        assert_eq!(format("rumap.yml"), Some(ConfigFormat::Yaml));
+        assert_eq!(format("~/.rumap"), None);
+      }
+    }
+
+    describe "#parse" {
+      it "parses TOML" {
+        let config = ConfigFormat::Toml.parse(r#"
+# comment
+[remap]
+"C-h" = { to = "Left", with = ["Shift"] }
+
+[in_app.emacs]
+"C-a" = { disable = true }
+"#).unwrap();
+        let config = serde_json::to_value(config).unwrap();
+        assert_eq!(config["remap"]["C-h"], json!({ "to": "Left", "with": ["Shift"] }));
+        assert_eq!(config["in_app"]["emacs"]["C-a"], json!({ "disable": true }));
+      }
+
+      it "parses YAML" {
+        let config = ConfigFormat::Yaml.parse(r#"
+# comment
+remap:
+  C-h: { to: Left, with: [Shift] }
+in_app:
+  emacs:
+    C-a:
+      disable: true
+"#).unwrap();
+        let config = serde_json::to_value(config).unwrap();
+        assert_eq!(config["remap"]["C-h"], json!({ "to": "Left", "with": ["Shift"] }));
+        assert_eq!(config["in_app"]["emacs"]["C-a"], json!({ "disable": true }));
+      }
+
+      it "reports line and column of errors" {
+        let position = |err: FormatError| (err.line, err.column);
+        assert_eq!(
+          position(ConfigFormat::Json.parse("{\n  \"remap\": {},\n  \"in_app\": x\n}").unwrap_err()),
+          (Some(3), Some(13))
+        );
+        assert_eq!(
+          position(ConfigFormat::Toml.parse("[remap]\n\"C-h\" = = 1\n").unwrap_err()),
+          (Some(2), Some(9))
+        );
+        assert_eq!(
+          position(ConfigFormat::Yaml.parse("remap: {}\nin_app: [\n").unwrap_err()),
+          (Some(3), Some(1))
+        );
+      }
+    }
+  }
+}
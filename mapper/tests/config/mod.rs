@@ -1 +1,3 @@
+pub mod dsl;
+pub mod format;
 pub mod parser;
@@ -1,7 +1,7 @@
 use speculate::speculate;
 
 use mapper::config::values;
-use mapper::config::{IsParser, Parser};
+use mapper::config::{InvalidConfigError, IsParser, Parser, Side};
 use mapper::*;
 use std::collections::BTreeMap;
 
@@ -43,62 +43,56 @@ speculate! {
           .unwrap();
       }
 
-      it "calculates all possible inapp keyinputs" {
-        let mut inapp_keyinputs = possible_keyinput_finder.find(
+      it "grabs each inapp key once with optional modifiers" {
+        let mut inapp_grabs = possible_keyinput_finder.find(
           Focus::Focused { application: Application::new("app".to_string()) }
         );
-        inapp_keyinputs.sort();
+        inapp_grabs.sort();
 
         // globalのやつも返す
+        let with = Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())]);
         let mut expect = vec![
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string())])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string())])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("c".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("c".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string())])),
-          KeyInput::new(Key::new("c".to_string()), Modifiers::new(vec![Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("c".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("a".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("a".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string())])),
-          KeyInput::new(Key::new("a".to_string()), Modifiers::new(vec![Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("a".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())])),
+          Grab::new(KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![]))).with_optional_modifiers(with.clone()),
+          Grab::new(KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![]))).with_optional_modifiers(with.clone()),
+          Grab::new(KeyInput::new(Key::new("c".to_string()), Modifiers::new(vec![]))).with_optional_modifiers(with.clone()),
+          Grab::new(KeyInput::new(Key::new("a".to_string()), Modifiers::new(vec![]))).with_optional_modifiers(with.clone()),
         ];
         expect.sort();
 
         assert_eq!(
-          inapp_keyinputs,
+          inapp_grabs,
           expect
         );
       }
 
-      it "calculates all possible global keyinputs" {
-        let mut global_keyinputs = possible_keyinput_finder.find(
+      it "grabs each global key once with optional modifiers" {
+        let mut global_grabs = possible_keyinput_finder.find(
           Focus::NoFocus
         );
-        global_keyinputs.sort();
+        global_grabs.sort();
 
+        let with = Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())]);
         let mut expect = vec![
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string())])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string())])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![Modifier::new("Mod2".to_string())])),
-          KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![Modifier::new("Shift".to_string()), Modifier::new("Mod2".to_string())])),
+          Grab::new(KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![]))).with_optional_modifiers(with.clone()),
+          Grab::new(KeyInput::new(Key::new("l".to_string()), Modifiers::new(vec![]))).with_optional_modifiers(with.clone()),
         ];
         expect.sort();
 
         assert_eq!(
-          global_keyinputs,
+          global_grabs,
           expect
         );
       }
+
+      it "covers every combination of optional modifiers" {
+        let grabs = possible_keyinput_finder.find(Focus::NoFocus);
+        let h = |ms: Vec<&str>| KeyInput::of("h".to_string(), ms.into_iter().map(|m| m.to_string()).collect());
+        assert!(grabs[0].covers(&h(vec![])));
+        assert!(grabs[0].covers(&h(vec!["Shift"])));
+        assert!(grabs[0].covers(&h(vec!["Shift", "Mod2"])));
+        assert!(!grabs[0].covers(&h(vec!["Control"])));
+        assert!(!grabs[0].covers(&h(vec!["Shift", "Control"])));
+      }
     }
 
     context "when same key is specified for global and inapp" {
@@ -129,21 +123,219 @@ speculate! {
           .unwrap();
       }
 
-      // FIXME ほんとはinappがglobalを上書きするようにしたい
+      // hはinappのkeybindが優先されるが、globalのwithで増えるkeyinputもinappのkeybindで変換されるのでgrabする
       it "returns global and inapp keyinputs" {
         let mut result = possible_keyinput_finder.find(Focus::Focused { application: Application::new("app".to_string()) });
         result.sort();
 
+        let h = KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![]));
         let mut expect = vec![
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("inapp".to_string())])),
-          KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![Modifier::new("global".to_string())])),
+          Grab::new(h.clone()).with_optional_modifiers(Modifiers::new(vec![Modifier::new("inapp".to_string())])),
+          Grab::new(h).with_optional_modifiers(Modifiers::new(vec![Modifier::new("global".to_string())])),
         ];
         expect.sort();
 
         assert_eq!(result, expect);
       }
     }
+
+    context "when inapp remap disables global remap" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C-a": { "to": "Home" },
+              "C-e": { "to": "End" }
+            },
+            "in_app": {
+              "Emacs": {
+                "C-a": { "disable": true }
+              }
+            }
+          }
+          "#
+        ).unwrap();
+
+        let possible_keyinput_finder = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_possible_keyinput_finder(config)
+          .unwrap();
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+      }
+
+      it "does not grab disabled keyinput in the application" {
+        assert_eq!(
+          possible_keyinput_finder.find(Focus::Focused { application: Application::new("Emacs".to_string()) }),
+          vec![Grab::new(stroke("C-e"))]
+        );
+      }
+
+      it "grabs disabled keyinput in other applications" {
+        assert_eq!(
+          possible_keyinput_finder.find(Focus::Focused { application: Application::new("Firefox".to_string()) }),
+          vec![Grab::new(stroke("C-a")), Grab::new(stroke("C-e"))]
+        );
+      }
+    }
+
+    context "when global remap has higher priority than disabling inapp remap" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C-a": { "to": "Home" }
+            },
+            "in_app": {
+              "Emacs": {
+                "C-a": { "disable": true }
+              }
+            },
+            "priority": { "remap": 1 }
+          }
+          "#
+        ).unwrap();
+
+        let possible_keyinput_finder = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_possible_keyinput_finder(config)
+          .unwrap();
+      }
+
+      it "grabs global keyinput" {
+        assert_eq!(
+          possible_keyinput_finder.find(Focus::Focused { application: Application::new("Emacs".to_string()) }),
+          vec![Grab::new(KeyInput::new(Key::new("C-a".to_string()), Modifiers::new(vec![])))]
+        );
+      }
+    }
+
+    context "when remap excludes applications" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {},
+            "in_app": {},
+            "windows": [
+              {
+                "class_not": ["Alacritty", "Steam"],
+                "remap": { "C-h": { "to": "BackSpace" } }
+              },
+              {
+                "class_only": ["chromium", "discord"],
+                "remap": { "Alt-c": { "to": "C-c" } }
+              }
+            ]
+          }
+          "#
+        ).unwrap();
+
+        let possible_keyinput_finder = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_possible_keyinput_finder(config)
+          .unwrap();
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+        let focused = |app: &str| Focus::Focused { application: Application::new(app.to_string()) };
+      }
+
+      it "grabs keyinputs only for matched applications" {
+        assert_eq!(possible_keyinput_finder.find(focused("Alacritty")), vec![]);
+        assert_eq!(possible_keyinput_finder.find(focused("Firefox")), vec![Grab::new(stroke("C-h"))]);
+        assert_eq!(possible_keyinput_finder.find(focused("discord")), vec![Grab::new(stroke("Alt-c")), Grab::new(stroke("C-h"))]);
+        assert_eq!(possible_keyinput_finder.find(Focus::NoFocus), vec![Grab::new(stroke("C-h"))]);
+      }
+    }
+
+    context "when sequence remap is configured" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "x f": {
+                "to": "o",
+                "with": ["Shift"]
+              },
+              "x k": {
+                "to": "w"
+              }
+            },
+            "in_app": {}
+          }
+          "#
+        ).unwrap();
+
+        let possible_keyinput_finder = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_possible_keyinput_finder(config)
+          .unwrap();
+      }
+
+      it "returns only first stroke without prefix" {
+        assert_eq!(
+          possible_keyinput_finder.find(Focus::NoFocus),
+          vec![Grab::new(KeyInput::new(Key::new("x".to_string()), Modifiers::new(vec![])))]
+        );
+      }
+
+      it "returns following strokes after prefix" {
+        let mut expect = vec![
+          Grab::new(KeyInput::new(Key::new("f".to_string()), Modifiers::new(vec![])))
+            .with_optional_modifiers(Modifiers::new(vec![Modifier::new("Shift".to_string())])),
+          Grab::new(KeyInput::new(Key::new("k".to_string()), Modifiers::new(vec![]))),
+        ];
+        expect.sort();
+
+        assert_eq!(
+          possible_keyinput_finder.find_in_sequence(
+            Focus::Focused { application: Application::new("app".to_string()) },
+            &[KeyInput::new(Key::new("x".to_string()), Modifiers::new(vec![]))]
+          ),
+          expect
+        );
+      }
+    }
+
+    context "when mode remap is configured" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "n": { "enter_mode": "nav" }
+            },
+            "in_app": {},
+            "modes": {
+              "nav": {
+                "h": { "to": "Left" },
+                "Escape": { "exit_mode": "nav" }
+              }
+            }
+          }
+          "#
+        ).unwrap();
+
+        let possible_keyinput_finder = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_possible_keyinput_finder(config)
+          .unwrap();
+      }
+
+      it "does not return mode keyinputs while mode is inactive" {
+        assert_eq!(
+          possible_keyinput_finder.find_in_modes(&[], Focus::NoFocus, &[]),
+          vec![Grab::new(KeyInput::new(Key::new("n".to_string()), Modifiers::new(vec![])))]
+        );
+      }
+
+      it "returns mode keyinputs while mode is active" {
+        assert_eq!(
+          possible_keyinput_finder.find_in_modes(&[Mode::new("nav".to_string())], Focus::NoFocus, &[]),
+          vec![
+            Grab::new(KeyInput::new(Key::new("Escape".to_string()), Modifiers::new(vec![]))),
+            Grab::new(KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![]))),
+            Grab::new(KeyInput::new(Key::new("n".to_string()), Modifiers::new(vec![]))),
+          ]
+        );
+      }
+    }
   }
 
   describe "build_keybind_for_focus" {
@@ -170,6 +362,12 @@ speculate! {
         let config = values::Config {
           remap: remaps,
           in_app: in_app,
+          modes: BTreeMap::new(),
+          chord_timeout: None,
+          priority: values::Priority::default(),
+          windows: vec![],
+          ignore_modifiers: vec![],
+          positions: BTreeMap::new(),
         };
 
         let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
@@ -225,6 +423,355 @@ speculate! {
       }
     }
 
+    context "when sequence remap is configured" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "x f": {
+                "execute": "global_execution"
+              }
+            },
+            "in_app": {}
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+      }
+
+      it "matches after prefix is typed" {
+        let x = KeyInput::new(Key::new("x".to_string()), Modifiers::new(vec![]));
+        let f = KeyInput::new(Key::new("f".to_string()), Modifiers::new(vec![]));
+
+        assert_eq!(keybind_for_focus.pressed(Focus::NoFocus, x.clone()), Some(Action::Prefix));
+        assert_eq!(keybind_for_focus.pressed(Focus::NoFocus, f.clone()), None);
+        assert_eq!(
+          keybind_for_focus.pressed_in_sequence(Focus::NoFocus, &[x], f),
+          Some(Action::Execution {
+            execution: "pressed/global_execution".to_string(),
+          })
+        );
+      }
+    }
+
+    context "when mode remap is configured" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "h": { "to": "BackSpace" },
+              "n": { "toggle_mode": "nav" }
+            },
+            "in_app": {},
+            "modes": {
+              "nav": {
+                "h": { "to": "Left" }
+              }
+            }
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+      }
+
+      it "builds mode actions" {
+        assert_eq!(
+          keybind_for_focus.pressed(
+            Focus::NoFocus,
+            KeyInput::new(Key::new("n".to_string()), Modifiers::new(vec![]))
+          ),
+          Some(Action::ToggleMode { mode: Mode::new("nav".to_string()) })
+        );
+      }
+
+      it "prefers active mode remap" {
+        let h = KeyInput::new(Key::new("h".to_string()), Modifiers::new(vec![]));
+        assert_eq!(
+          keybind_for_focus.pressed_in_modes(&[Mode::new("nav".to_string())], Focus::NoFocus, &[], h.clone()),
+          Some(Action::Key {
+            key_input: KeyInput::new(Key::new("Left".to_string()), Modifiers::new(vec![]))
+          })
+        );
+        assert_eq!(
+          keybind_for_focus.pressed_in_modes(&[], Focus::NoFocus, &[], h),
+          Some(Action::Key {
+            key_input: KeyInput::new(Key::new("BackSpace".to_string()), Modifiers::new(vec![]))
+          })
+        );
+      }
+    }
+
+    context "when tap/hold remap is configured" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "Caps_Lock": { "tap": "Escape", "hold": "Control" }
+            },
+            "in_app": {}
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+      }
+
+      it "builds tap/hold action with default timeout" {
+        assert_eq!(
+          keybind_for_focus.pressed(
+            Focus::NoFocus,
+            KeyInput::new(Key::new("Caps_Lock".to_string()), Modifiers::new(vec![]))
+          ),
+          Some(Action::TapHold {
+            tap: KeyInput::new(Key::new("Escape".to_string()), Modifiers::new(vec![])),
+            hold: Modifier::new("Control".to_string()),
+            timeout: std::time::Duration::from_millis(mapper::config::DEFAULT_HOLD_TIMEOUT),
+          })
+        );
+      }
+    }
+
+    context "when sequence action is configured" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C-t": { "sequence": ["C-a", { "to": "k", "delay": 10 }] }
+            },
+            "in_app": {}
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+      }
+
+      it "builds sequence action with per-step delays" {
+        assert_eq!(
+          keybind_for_focus.pressed(Focus::NoFocus, stroke("C-t")),
+          Some(Action::Sequence {
+            steps: vec![
+              Step::new(stroke("C-a")),
+              Step::new(stroke("k"))
+                .with_delay(std::time::Duration::from_millis(10)),
+            ]
+          })
+        );
+      }
+    }
+
+    context "when chord remap is configured" {
+      before {
+        let config: mapper::config::Config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "j+k": { "to": "Escape" }
+            },
+            "in_app": {},
+            "chord_timeout": 30
+          }
+          "#
+        ).unwrap();
+        let parser = Parser::build(&mapper::mock::StringIntoDomain);
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+      }
+
+      it "builds chord keybind with configured timeout" {
+        let keybind_for_focus = parser.build_keybind_for_focus(config).unwrap();
+        assert_eq!(
+          keybind_for_focus.pressed(Focus::NoFocus, stroke("j")),
+          Some(Action::Chord { timeout: std::time::Duration::from_millis(30) })
+        );
+        assert_eq!(
+          keybind_for_focus.pressed_in_sequence(Focus::NoFocus, &[stroke("k")], stroke("j")),
+          Some(Action::Key { key_input: stroke("Escape") })
+        );
+      }
+
+      it "watches every key of the chord" {
+        let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+        assert_eq!(
+          possible_keyinput_finder.find(Focus::NoFocus),
+          vec![Grab::new(stroke("j")), Grab::new(stroke("k"))]
+        );
+      }
+    }
+
+    context "when inapp remap disables global remap" {
+      before {
+        let config: mapper::config::Config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C-a": { "to": "Home" }
+            },
+            "in_app": {
+              "Emacs": {
+                "C-a": { "disable": true }
+              }
+            }
+          }
+          "#
+        ).unwrap();
+        let emacs = Focus::Focused { application: Application::new("Emacs".to_string()) };
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+      }
+
+      it "does not fall back to global remap in the application" {
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+        assert_eq!(keybind_for_focus.pressed(emacs, stroke("C-a")), None);
+        assert_eq!(
+          keybind_for_focus.pressed(Focus::NoFocus, stroke("C-a")),
+          Some(Action::Key { key_input: stroke("Home") })
+        );
+      }
+
+      it "uses global remap when it has higher priority" {
+        let config = mapper::config::Config {
+          priority: values::Priority { remap: Some(1), ..Default::default() },
+          ..config
+        };
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+        assert_eq!(
+          keybind_for_focus.pressed(emacs, stroke("C-a")),
+          Some(Action::Key { key_input: stroke("Home") })
+        );
+      }
+    }
+
+    context "when remap excludes applications" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {},
+            "in_app": {},
+            "windows": [
+              {
+                "class_not": ["Alacritty"],
+                "remap": { "C-h": { "to": "BackSpace" } }
+              }
+            ]
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+        let focused = |app: &str| Focus::Focused { application: Application::new(app.to_string()) };
+      }
+
+      it "does not match in excluded applications" {
+        assert_eq!(keybind_for_focus.pressed(focused("Alacritty"), stroke("C-h")), None);
+      }
+
+      it "matches in other applications" {
+        assert_eq!(
+          keybind_for_focus.pressed(focused("Firefox"), stroke("C-h")),
+          Some(Action::Key { key_input: stroke("BackSpace") })
+        );
+      }
+    }
+
+    context "when several application patterns match" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {},
+            "in_app": {
+              "jetbrains-*": { "C-d": { "to": "glob" } },
+              "jetbrains-clion": { "C-d": { "to": "exact" } }
+            },
+            "windows": [
+              {
+                "class_only": ["/^jetbrains-/"],
+                "remap": { "C-d": { "to": "regex" }, "C-w": { "to": "regex" } }
+              }
+            ]
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+        let focused = |app: &str| Focus::Focused { application: Application::new(app.to_string()) };
+        let pressed = |app: &str, key: &str| keybind_for_focus.pressed(focused(app), stroke(key));
+      }
+
+      it "prefers exact match, glob and regex in this order" {
+        assert_eq!(pressed("jetbrains-clion", "C-d"), Some(Action::Key { key_input: stroke("exact") }));
+        assert_eq!(pressed("jetbrains-idea-ce", "C-d"), Some(Action::Key { key_input: stroke("glob") }));
+        assert_eq!(pressed("jetbrains-idea-ce", "C-w"), Some(Action::Key { key_input: stroke("regex") }));
+        assert_eq!(pressed("emacs", "C-d"), None);
+      }
+    }
+
+    context "when window title is specified" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {},
+            "in_app": {
+              "Alacritty": { "C-d": { "to": "terminal" } }
+            },
+            "windows": [
+              {
+                "class_only": ["Alacritty"],
+                "title_only": ["*vim*"],
+                "remap": { "C-d": { "to": "vim" } }
+              }
+            ]
+          }
+          "#
+        ).unwrap();
+
+        let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
+          .build_keybind_for_focus(config)
+          .unwrap();
+        let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+        let titled = |title: &str| Focus::Focused {
+          application: Application::new("Alacritty".to_string()).with_title(title.to_string()),
+        };
+      }
+
+      it "prefers remap for the title to remap for the application" {
+        assert_eq!(
+          keybind_for_focus.pressed(titled("nvim README.md"), stroke("C-d")),
+          Some(Action::Key { key_input: stroke("vim") })
+        );
+        assert_eq!(
+          keybind_for_focus.pressed(titled("zsh"), stroke("C-d")),
+          Some(Action::Key { key_input: stroke("terminal") })
+        );
+      }
+    }
+
     context "when global remap and inapp remap have common target keyinput" {
       before {
         let global_remaps = values::Remaps(maplit::btreemap! {
@@ -246,7 +793,13 @@ speculate! {
         };
         let config = values::Config {
           remap: global_remaps,
-          in_app: in_app,
+          in_app,
+          modes: BTreeMap::new(),
+          chord_timeout: None,
+          priority: values::Priority::default(),
+          windows: vec![],
+          ignore_modifiers: vec![],
+          positions: BTreeMap::new(),
         };
         let keybind_for_focus = Parser::build(&mapper::mock::StringIntoDomain)
           .build_keybind_for_focus(config)
@@ -268,4 +821,84 @@ speculate! {
       }
     }
   }
+
+  describe "check" {
+    context "when application patterns are invalid" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": { "a": { "to": "b" } },
+            "in_app": { "/[/": { "c": { "to": "d" } } },
+            "windows": [
+              { "class_only": ["chromium"], "remap": {} },
+              { "class_not": ["/(/"], "remap": {} }
+            ]
+          }
+          "#
+        ).unwrap();
+      }
+
+      it "reports all of them with their sections" {
+        let errors = Parser::build(&mapper::mock::StringIntoDomain).check(config);
+        let sections: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+          sections,
+          vec![
+            "in_app[/[/]: invalid application pattern `/[/`".to_string(),
+            "windows[1]: invalid application pattern `/(/`".to_string(),
+          ]
+        );
+      }
+    }
+
+    context "when key inputs are malformed" {
+      before {
+        let config = serde_json::from_str(
+          r#"
+          {
+            "remap": {
+              "C--x": { "to": "b" },
+              "j++k": { "to": "c" },
+              "a": { "to": "C-" }
+            },
+            "in_app": {}
+          }
+          "#
+        ).unwrap();
+      }
+
+      it "reports structural errors with the side they are on" {
+        let errors = Parser::build(&mapper::mock::StringIntoDomain).check(config);
+        let kinds: Vec<(InvalidConfigError, Option<Side>)> = errors
+          .iter()
+          .map(|error| (error.kind().clone(), error.location().unwrap().side))
+          .collect();
+        assert_eq!(
+          kinds,
+          vec![
+            (InvalidConfigError::EmptyModifier("C--x".to_string()), Some(Side::From)),
+            (InvalidConfigError::MissingKey("C-".to_string()), Some(Side::To)),
+            (InvalidConfigError::EmptyChordKey("j++k".to_string()), Some(Side::From)),
+          ]
+        );
+      }
+    }
+
+    context "when config is written in DSL" {
+      before {
+        let config = mapper::config::dsl::parse_dsl(
+          "remap 'a', to: 'b'\nwindow class_only: 'emacs' do\n  remap 'C--x', to: 'c'\nend\n",
+        ).unwrap();
+      }
+
+      it "reports the position of the remap" {
+        let errors = Parser::build(&mapper::mock::StringIntoDomain).check(config);
+        assert_eq!(
+          errors.iter().map(|error| error.to_string()).collect::<Vec<_>>(),
+          vec!["3:3: in_app[emacs] `C--x` (from): empty modifier in `C--x`".to_string()]
+        );
+      }
+    }
+  }
 }
@@ -0,0 +1,33 @@
+use speculate::speculate;
+
+speculate! {
+  describe "ActionKeyBind" {
+    before {
+      use mapper::{Action, ActionKeyBind, KeyBind, KeyInput, Mode};
+
+      let from = KeyInput::of("n".to_string(), vec!["Alt".to_string()]);
+      let action_keybind: ActionKeyBind<String, String, String> = ActionKeyBind::new(
+        from.clone(),
+        Action::EnterMode { mode: Mode::new("nav".to_string()) }
+      );
+    }
+
+    it "returns action when equivalent key is pressed" {
+      assert_eq!(
+        action_keybind.pressed(&from),
+        Some(Action::EnterMode { mode: Mode::new("nav".to_string()) })
+      );
+    }
+
+    it "does not match superset key" {
+      assert_eq!(
+        action_keybind.pressed(&KeyInput::of("n".to_string(), vec!["Alt".to_string(), "Shift".to_string()])),
+        None
+      );
+    }
+
+    it "ignores release" {
+      assert_eq!(action_keybind.released(&from), None);
+    }
+  }
+}
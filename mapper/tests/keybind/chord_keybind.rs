@@ -0,0 +1,54 @@
+use speculate::speculate;
+
+speculate! {
+  describe "ChordKeyBind" {
+    before {
+      use mapper::mock::MockKeyBind;
+      use mapper::{ChordKeyBind, Key, KeyBind, KeyInput, Modifiers};
+      use std::time::Duration;
+
+      let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+      let chord_keybind = ChordKeyBind::new(
+        vec![stroke("j"), stroke("k")],
+        Box::new(MockKeyBind {
+          from: "k".to_string(),
+          execution: "exec".to_string()
+        }),
+        Duration::from_millis(50)
+      );
+    }
+
+    describe "#pressed_in_sequence" {
+      it "returns Chord while waiting for the other keys" {
+        let chord = Some(mapper::Action::Chord { timeout: Duration::from_millis(50) });
+        assert_eq!(chord_keybind.pressed(&stroke("j")), chord);
+        assert_eq!(chord_keybind.pressed(&stroke("k")), chord);
+      }
+
+      it "delegates to inner keybind when all keys are pressed in any order" {
+        let action = Some(mapper::Action::Execution { execution: "pressed/exec".to_string() });
+        assert_eq!(chord_keybind.pressed_in_sequence(&[stroke("j")], &stroke("k")), action);
+        assert_eq!(chord_keybind.pressed_in_sequence(&[stroke("k")], &stroke("j")), action);
+      }
+
+      it "does not match other keys or the same key twice" {
+        assert_eq!(chord_keybind.pressed(&stroke("l")), None);
+        assert_eq!(chord_keybind.pressed_in_sequence(&[stroke("j")], &stroke("j")), None);
+        assert_eq!(chord_keybind.pressed_in_sequence(&[stroke("j")], &stroke("l")), None);
+      }
+    }
+
+    describe "#released_in_sequence" {
+      it "delegates to inner keybind when the chord is completed" {
+        assert_eq!(
+          chord_keybind.released_in_sequence(&[stroke("j")], &stroke("k")),
+          Some(mapper::Action::Execution { execution: "released/exec".to_string() })
+        );
+      }
+
+      it "ignores release before the chord is completed" {
+        assert_eq!(chord_keybind.released(&stroke("j")), None);
+      }
+    }
+  }
+}
@@ -1,11 +1,42 @@
+use mapper::{Action, KeyBind, KeyInput, Keymap, Trigger};
 use speculate::speculate;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// 問い合わせられた回数を数えるkeybind
+struct CountingKeyBind {
+  keymap: Keymap<String, String>,
+  count: Rc<Cell<usize>>,
+}
+
+impl KeyBind<String, String, String> for CountingKeyBind {
+  fn pressed(
+    &self,
+    key_input: &KeyInput<String, String>,
+  ) -> Option<Action<String, String, String>> {
+    self.count.set(self.count.get() + 1);
+    self.keymap.pressed(key_input)
+  }
+
+  fn released(
+    &self,
+    key_input: &KeyInput<String, String>,
+  ) -> Option<Action<String, String, String>> {
+    self.count.set(self.count.get() + 1);
+    self.keymap.released(key_input)
+  }
+
+  fn triggers(&self) -> Vec<Trigger<String, String>> {
+    KeyBind::<String, String, String>::triggers(&self.keymap)
+  }
+}
 
 speculate! {
   describe "CompositKeyBind" {
     context "when two keybind are composed" {
       before {
         use mapper::mock::MockKeyBind;
-        use mapper::{Action, CompositKeyBind, Key, KeyBind, KeyInput, Modifiers};
+        use mapper::{CompositKeyBind, Key, Modifiers};
 
         let keybind_1 = MockKeyBind {
           from: "bind_1".to_string(),
@@ -37,5 +68,132 @@ speculate! {
         );
       }
     }
+
+    context "when keybinds with nested modifiers are composed" {
+      before {
+        use mapper::CompositKeyBind;
+
+        let input = |k: &str, ms: &[&str]| {
+          KeyInput::of(k.to_string(), ms.iter().map(|m| m.to_string()).collect())
+        };
+        let keymap = |from: KeyInput<String, String>, to: &str| -> Box<dyn KeyBind<String, String, String>> {
+          Box::new(Keymap::new(from, KeyInput::of(to.to_string(), vec![])))
+        };
+        let output = |k: &str, ms: &[&str]| -> Option<Action<String, String, String>> {
+          Some(Action::Key { key_input: input(k, ms) })
+        };
+        // BTreeMapの順と同じく、modifierの少ないものから並べる
+        let broad_first = CompositKeyBind::new(vec![
+          keymap(input("h", &[]), "Left"),
+          keymap(input("h", &["C"]), "BackSpace"),
+          keymap(input("h", &["C", "S"]), "Delete"),
+        ]);
+        let specific_first = CompositKeyBind::new(vec![
+          keymap(input("h", &["C", "S"]), "Delete"),
+          keymap(input("h", &["C"]), "BackSpace"),
+          keymap(input("h", &[]), "Left"),
+        ]);
+      }
+
+      it "prefers C-S-h over C-h and h" {
+        assert_eq!(broad_first.pressed(&input("h", &["C", "S"])), output("Delete", &[]));
+        assert_eq!(specific_first.pressed(&input("h", &["C", "S"])), output("Delete", &[]));
+      }
+
+      it "prefers C-h over h" {
+        assert_eq!(broad_first.pressed(&input("h", &["C"])), output("BackSpace", &[]));
+        assert_eq!(specific_first.pressed(&input("h", &["C"])), output("BackSpace", &[]));
+      }
+
+      it "uses h when no modifier is pressed" {
+        assert_eq!(broad_first.pressed(&input("h", &[])), output("Left", &[]));
+        assert_eq!(specific_first.pressed(&input("h", &[])), output("Left", &[]));
+      }
+
+      it "passes extra modifiers through the most specific match" {
+        assert_eq!(broad_first.pressed(&input("h", &["C", "M"])), output("BackSpace", &["M"]));
+        assert_eq!(specific_first.pressed(&input("h", &["C", "M"])), output("BackSpace", &["M"]));
+        assert_eq!(broad_first.released(&input("h", &["C", "S"])), output("Delete", &[]));
+      }
+    }
+
+    context "when a sequence and a chord start with a stroke more specific than a keymap" {
+      before {
+        use mapper::{ChordKeyBind, CompositKeyBind, SequenceKeyBind};
+        use std::time::Duration;
+
+        let input = |k: &str, ms: &[&str]| {
+          KeyInput::of(k.to_string(), ms.iter().map(|m| m.to_string()).collect())
+        };
+        let keymap = |from: KeyInput<String, String>, to: &str| -> Box<dyn KeyBind<String, String, String>> {
+          Box::new(Keymap::new(from, KeyInput::of(to.to_string(), vec![])))
+        };
+        let timeout = Duration::from_millis(50);
+        // 素のhのkeymapを先に書いても、S-hで始まるsequenceとchordが優先される
+        let composit = CompositKeyBind::new(vec![
+          keymap(input("h", &[]), "Left"),
+          Box::new(SequenceKeyBind::new(vec![input("h", &["S"])], keymap(input("x", &[]), "y"))),
+          keymap(input("j", &[]), "Down"),
+          Box::new(ChordKeyBind::new(
+            vec![input("j", &["S"]), input("k", &["S"])],
+            keymap(input("k", &["S"]), "Escape"),
+            timeout,
+          )),
+        ]);
+      }
+
+      it "starts the sequence and the chord" {
+        assert_eq!(composit.pressed(&input("h", &["S"])), Some(Action::Prefix));
+        assert_eq!(composit.pressed(&input("j", &["S"])), Some(Action::Chord { timeout }));
+      }
+
+      it "uses the keymap without modifiers" {
+        assert_eq!(composit.pressed(&input("h", &[])), Some(Action::Key { key_input: input("Left", &[]) }));
+        assert_eq!(composit.pressed(&input("j", &[])), Some(Action::Key { key_input: input("Down", &[]) }));
+      }
+    }
+
+    context "when many keybinds are composed" {
+      before {
+        use mapper::CompositKeyBind;
+
+        let input = |k: &str, ms: &[&str]| {
+          KeyInput::of(k.to_string(), ms.iter().map(|m| m.to_string()).collect())
+        };
+        let count = Rc::new(Cell::new(0));
+        // key_0 .. key_{size-1} をそれぞれ素の入力とC-付きの入力でremapする
+        let composit = |size: usize| {
+          let keybindings: Vec<Box<dyn KeyBind<String, String, String>>> = (0..size)
+            .flat_map(|i| vec![(i, vec![]), (i, vec!["C"])])
+            .map(|(i, ms)| -> Box<dyn KeyBind<String, String, String>> {
+              Box::new(CountingKeyBind {
+                keymap: Keymap::new(input(&format!("key_{}", i), &ms), input("Return", &[])),
+                count: count.clone(),
+              })
+            })
+            .collect();
+          CompositKeyBind::new(keybindings)
+        };
+        let consulted = |keybind: &CompositKeyBind<String, String, String>, key_input: KeyInput<String, String>| {
+          count.set(0);
+          let action = keybind.pressed(&key_input);
+          (action.is_some(), count.get())
+        };
+      }
+
+      it "consults the same number of keybinds regardless of config size" {
+        let small = composit(10);
+        let large = composit(5000);
+        for key_input in [input("key_3", &[]), input("key_3", &["C"]), input("key_3", &["C", "M"])] {
+          let (small_matched, small_count) = consulted(&small, key_input.clone());
+          let (large_matched, large_count) = consulted(&large, key_input);
+          assert!(small_matched && large_matched);
+          assert_eq!(small_count, large_count);
+          assert!(large_count <= 2);
+        }
+        assert_eq!(consulted(&large, input("key_4999", &["C"])), (true, 1));
+        assert_eq!(consulted(&large, input("unknown", &[])), (false, 0));
+      }
+    }
   }
 }
@@ -2,7 +2,9 @@ use speculate::speculate;
 
 use mapper::mock::MockKeyBind;
 use mapper::{
-  Application, Focus, IsKeyBindForFocus, Key, KeyBind, KeyBindForFocus, KeyInput, Modifiers,
+  Action, ActionKeyBind, Application, ChordKeyBind, CompositKeyBind, Conflict, Focus,
+  IsKeyBindForFocus, Key, KeyBind, KeyBindForFocus, KeyInput, Keymap, Layer, Modifiers, Precedence,
+  SequenceKeyBind, Trigger,
 };
 
 speculate! {
@@ -143,4 +145,168 @@ speculate! {
       }
     }
   }
+
+  describe "KeyBindForFocus#conflicts" {
+    before {
+      type Keybinds = Vec<Box<dyn KeyBind<String, String, String>>>;
+      let keymap = |from: KeyInput<String, String>, to: &str| -> Box<dyn KeyBind<String, String, String>> {
+        Box::new(Keymap::new(from, KeyInput::of(to.to_string(), vec![])))
+      };
+      let key = |k: &str| KeyInput::of(k.to_string(), vec![]);
+    }
+
+    context "when a broad rule comes before a rule with more modifiers" {
+      before {
+        let control = |k: &str| KeyInput::of(k.to_string(), vec!["C".to_string()]);
+        let global: Keybinds = vec![keymap(key("h"), "Left"), keymap(control("h"), "BackSpace")];
+        let keybind_for_focus: KeyBindForFocus<String, String, String, String> = KeyBindForFocus::new(
+          Box::new(CompositKeyBind::new(global)),
+          maplit::btreemap! {},
+        );
+      }
+
+      it "reports nothing because the rule with more modifiers wins" {
+        assert_eq!(keybind_for_focus.conflicts(), vec![]);
+      }
+    }
+
+    context "when a rule matching extra modifiers comes before an exact rule" {
+      before {
+        let global: Keybinds = vec![
+          keymap(key("h"), "Left"),
+          Box::new(ActionKeyBind::new(key("h"), Action::Disabled)),
+        ];
+        let keybind_for_focus: KeyBindForFocus<String, String, String, String> = KeyBindForFocus::new(
+          Box::new(CompositKeyBind::new(global)),
+          maplit::btreemap! {},
+        );
+      }
+
+      it "reports the later rule as shadowed" {
+        assert_eq!(
+          keybind_for_focus.conflicts(),
+          vec![Conflict::Shadowed {
+            layer: Layer::Global,
+            trigger: Trigger::exact(key("h")),
+            by: Trigger::superset(key("h")),
+          }]
+        );
+      }
+    }
+
+    context "when the same input is bound twice" {
+      before {
+        let global: Keybinds = vec![
+          keymap(key("a"), "b"),
+          Box::new(ActionKeyBind::new(key("j"), Action::Disabled)),
+          keymap(key("a"), "c"),
+        ];
+        let keybind_for_focus: KeyBindForFocus<String, String, String, String> = KeyBindForFocus::new(
+          Box::new(CompositKeyBind::new(global)),
+          maplit::btreemap! {},
+        );
+      }
+
+      it "reports the duplicate" {
+        assert_eq!(
+          keybind_for_focus.conflicts(),
+          vec![Conflict::Duplicate {
+            layer: Layer::Global,
+            trigger: Trigger::superset(key("a")),
+          }]
+        );
+      }
+    }
+
+    context "when a single-stroke rule takes the first stroke of a sequence or a chord" {
+      before {
+        let shift = |k: &str| KeyInput::of(k.to_string(), vec!["S".to_string()]);
+        let timeout = std::time::Duration::from_millis(50);
+        let global: Keybinds = vec![
+          keymap(key("h"), "Left"),
+          Box::new(SequenceKeyBind::new(vec![key("h")], keymap(key("x"), "y"))),
+          // modifierの多いstrokeで始まるsequenceは、先にある素のhより優先される
+          Box::new(SequenceKeyBind::new(vec![shift("h")], keymap(key("x"), "y"))),
+          Box::new(ChordKeyBind::new(vec![key("j"), key("k")], keymap(key("k"), "Escape"), timeout)),
+          keymap(key("j"), "Down"),
+        ];
+        let keybind_for_focus: KeyBindForFocus<String, String, String, String> = KeyBindForFocus::new(
+          Box::new(CompositKeyBind::new(global)),
+          maplit::btreemap! {},
+        );
+      }
+
+      it "reports whichever comes later as shadowed" {
+        assert_eq!(
+          keybind_for_focus.conflicts(),
+          vec![
+            Conflict::Shadowed {
+              layer: Layer::Global,
+              trigger: Trigger::superset(key("x")).with_prefix(&[key("h")]),
+              by: Trigger::superset(key("h")),
+            },
+            Conflict::Shadowed {
+              layer: Layer::Global,
+              trigger: Trigger::superset(key("j")),
+              by: Trigger::superset(key("k")).with_chord(vec![key("j"), key("k")]),
+            },
+          ]
+        );
+      }
+    }
+
+    context "when an in-app rule takes the first stroke of a global sequence" {
+      before {
+        let control = |k: &str| KeyInput::of(k.to_string(), vec!["C".to_string()]);
+        let global: Keybinds = vec![Box::new(SequenceKeyBind::new(vec![control("x")], keymap(key("f"), "o")))];
+        let in_app: Keybinds = vec![keymap(key("x"), "y")];
+        let app = Application::new("app".to_string());
+        let keybind_for_focus = KeyBindForFocus::new(
+          Box::new(CompositKeyBind::new(global)),
+          maplit::btreemap! { app.clone() => Box::new(CompositKeyBind::new(in_app)) as Box<dyn KeyBind<String, String, String>> },
+        );
+      }
+
+      it "reports the in-app rule hiding the global sequence" {
+        assert_eq!(
+          keybind_for_focus.conflicts(),
+          vec![Conflict::ShadowsGlobal {
+            layer: Layer::Application(app),
+            trigger: Trigger::superset(key("x")),
+            global: Trigger::superset(key("f")).with_prefix(&[control("x")]),
+          }]
+        );
+      }
+    }
+
+    context "when an in-app rule overrides a global rule" {
+      before {
+        let control = |k: &str| KeyInput::of(k.to_string(), vec!["C".to_string()]);
+        let global: Keybinds = vec![keymap(control("a"), "Home"), keymap(key("b"), "c")];
+        let in_app: Keybinds = vec![keymap(key("a"), "x")];
+        let app = Application::new("app".to_string());
+        let keybind_for_focus = KeyBindForFocus::new(
+          Box::new(CompositKeyBind::new(global)),
+          maplit::btreemap! { app.clone() => Box::new(CompositKeyBind::new(in_app)) as Box<dyn KeyBind<String, String, String>> },
+        );
+      }
+
+      it "reports the in-app rule shadowing the global one" {
+        assert_eq!(
+          keybind_for_focus.conflicts(),
+          vec![Conflict::ShadowsGlobal {
+            layer: Layer::Application(app),
+            trigger: Trigger::superset(key("a")),
+            global: Trigger::superset(control("a")),
+          }]
+        );
+      }
+
+      it "does not report it when the global layer has higher priority" {
+        let keybind_for_focus = keybind_for_focus
+          .with_precedence(Precedence::new().with_priority(Layer::Global, 1));
+        assert_eq!(keybind_for_focus.conflicts(), vec![]);
+      }
+    }
+  }
 }
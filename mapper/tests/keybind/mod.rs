@@ -1,2 +1,5 @@
+mod action_keybind;
+mod chord_keybind;
 mod composit_keybind;
 mod keybind_for_focus;
+mod sequence_keybind;
@@ -0,0 +1,58 @@
+use speculate::speculate;
+
+speculate! {
+  describe "SequenceKeyBind" {
+    before {
+      use mapper::mock::MockKeyBind;
+      use mapper::{Key, KeyBind, KeyInput, Modifiers, SequenceKeyBind};
+
+      let stroke = |key: &str| KeyInput::new(Key::new(key.to_string()), Modifiers::new(vec![]));
+      let sequence_keybind = SequenceKeyBind::new(
+        vec![stroke("prefix_1"), stroke("prefix_2")],
+        Box::new(MockKeyBind {
+          from: "last".to_string(),
+          execution: "exec".to_string()
+        })
+      );
+    }
+
+    describe "#pressed_in_sequence" {
+      it "returns Prefix while prefix strokes are typed in order" {
+        assert_eq!(sequence_keybind.pressed(&stroke("prefix_1")), Some(mapper::Action::Prefix));
+        assert_eq!(
+          sequence_keybind.pressed_in_sequence(&[stroke("prefix_1")], &stroke("prefix_2")),
+          Some(mapper::Action::Prefix)
+        );
+      }
+
+      it "delegates last stroke to inner keybind" {
+        assert_eq!(
+          sequence_keybind.pressed_in_sequence(&[stroke("prefix_1"), stroke("prefix_2")], &stroke("last")),
+          Some(mapper::Action::Execution { execution: "pressed/exec".to_string() })
+        );
+      }
+
+      it "does not match when strokes are out of order" {
+        assert_eq!(sequence_keybind.pressed(&stroke("prefix_2")), None);
+        assert_eq!(sequence_keybind.pressed(&stroke("last")), None);
+        assert_eq!(
+          sequence_keybind.pressed_in_sequence(&[stroke("prefix_1")], &stroke("last")),
+          None
+        );
+      }
+    }
+
+    describe "#released_in_sequence" {
+      it "delegates last stroke to inner keybind" {
+        assert_eq!(
+          sequence_keybind.released_in_sequence(&[stroke("prefix_1"), stroke("prefix_2")], &stroke("last")),
+          Some(mapper::Action::Execution { execution: "released/exec".to_string() })
+        );
+      }
+
+      it "ignores release of prefix strokes" {
+        assert_eq!(sequence_keybind.released(&stroke("prefix_1")), None);
+      }
+    }
+  }
+}
@@ -1,3 +1,4 @@
+mod application_matcher;
 mod config;
 mod key;
 mod keybind;
@@ -35,10 +35,10 @@ speculate! {
           assert_eq!(state.application, Some(mapper::Application::new("next_app".to_string())))
         }
 
-        it "ungrab and grab keys" {
+        it "does not regrab when grabbed keys are unchanged" {
           state.run();
-          assert_eq!(*event_source.ungrabbed_count.lock().unwrap(), 1);
-          assert_eq!(event_source.grabbed_keys.lock().unwrap().len(), 2); // 起動時に一回、application changedのときに一回
+          assert_eq!(*event_source.ungrabbed_keys.lock().unwrap(), Vec::<Vec<Grab<String, String>>>::new());
+          assert_eq!(*event_source.grabbed_keys.lock().unwrap(), Vec::<Vec<Grab<String, String>>>::new());
         }
       }
 
@@ -121,6 +121,895 @@ speculate! {
           );
         }
       }
+
+      context "when grabbed key is pressed, and no keybind matches" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+
+          let executor = MockShellCommandExecutor::new();
+          let possible_keyinput_finder = mapper::PossibleKeyinputFinder::new(maplit::btreemap!{});
+          let key_handler = MockKeyHandler::new();
+
+          let from = KeyInput::of(String::from("a"), vec![String::from("Alt")]);
+          let to = KeyInput::of(String::from("b"), vec![String::from("Control")]);
+
+          let keybind_for_focus = MockKeyBindForFocus::new(
+            maplit::btreemap!{
+              (Focus::NoFocus, from.clone()) => Action::Key { key_input: to.clone() }
+            },
+            maplit::btreemap!{},
+          );
+        }
+
+        context "and the modifiers do not match" {
+          before {
+            let unbound = KeyInput::of(String::from("a"), vec![String::from("Alt"), String::from("Shift")]);
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: unbound.clone() },
+              Event::KeyReleased { key_input: unbound.clone() },
+            ]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source,
+              key_handler.clone(),
+              executor
+            );
+          }
+
+          it "passes the original key through" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![unbound.clone()]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![unbound]);
+          }
+        }
+
+        context "and only the press is bound" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: from.clone() },
+              Event::KeyReleased { key_input: from.clone() },
+            ]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source,
+              key_handler.clone(),
+              executor
+            );
+          }
+
+          it "releases the key sent on the press" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![to.clone()]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![to]);
+          }
+        }
+      }
+
+      context "when a bound key is pressed and modifiers change before release" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+
+          let executor = MockShellCommandExecutor::new();
+          let possible_keyinput_finder = mapper::PossibleKeyinputFinder::new(maplit::btreemap!{});
+          let key_handler = MockKeyHandler::new();
+
+          let from = KeyInput::of(String::from("h"), vec![String::from("Control")]);
+          let to: KeyInput<String, String> = KeyInput::of(String::from("BackSpace"), vec![]);
+
+          let keybind_for_focus = MockKeyBindForFocus::new(
+            maplit::btreemap!{
+              (Focus::NoFocus, from.clone()) => Action::Key { key_input: to.clone() }
+            },
+            maplit::btreemap!{},
+          );
+        }
+
+        context "and the key is released" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: from.clone() },
+              Event::KeyReleased { key_input: KeyInput::of(String::from("h"), vec![]) },
+            ]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source,
+              key_handler.clone(),
+              executor
+            );
+          }
+
+          it "releases the key sent on the press" {
+            state.run();
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![to]);
+          }
+        }
+
+        context "and the focus changes before the key is released" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: from.clone() },
+              Event::ApplicationChanged { next_application: Some(Application::new("next_app".to_string())) },
+              Event::KeyReleased { key_input: KeyInput::of(String::from("h"), vec![]) },
+            ]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source,
+              key_handler.clone(),
+              executor
+            );
+          }
+
+          it "releases the key once when the focus changes" {
+            state.run();
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![to]);
+          }
+        }
+
+        context "and the event source ends before the key is released" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: from.clone() },
+            ]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source,
+              key_handler.clone(),
+              executor
+            );
+          }
+
+          it "releases the key on shutdown" {
+            state.run();
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![to]);
+          }
+        }
+      }
+
+      context "when only the window title changes while a key is held and a prefix is armed" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "a": { "to": "b" },
+                "x f": { "to": "o" }
+              },
+              "in_app": {}
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let key_handler = MockKeyHandler::new();
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+          let terminal = |title: &str| Some(Application::new("terminal".to_string()).with_title(title.to_string()));
+
+          let event_source = MockEventSource::new(vec![
+            Event::ApplicationChanged { next_application: terminal("vim") },
+            Event::KeyPressed { key_input: stroke("a") },
+            Event::KeyPressed { key_input: stroke("x") },
+            Event::ApplicationChanged { next_application: terminal("vim [+]") },
+            Event::KeyPressed { key_input: stroke("f") },
+            Event::KeyReleased { key_input: stroke("f") },
+            Event::KeyReleased { key_input: stroke("a") },
+          ]);
+          let mut state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source,
+            key_handler.clone(),
+            MockShellCommandExecutor::new()
+          );
+        }
+
+        it "keeps the held key and the sequence" {
+          state.run();
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("b"), stroke("o")]);
+          assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("o"), stroke("b")]);
+          assert_eq!(state.application, terminal("vim [+]"));
+        }
+      }
+
+      context "when sequence keybind exists" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "x f": { "to": "o" }
+              },
+              "in_app": {}
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let executor = MockShellCommandExecutor::new();
+          let key_handler = MockKeyHandler::new();
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+        }
+
+        context "and the sequence is completed" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: stroke("x") },
+              Event::KeyReleased { key_input: stroke("x") },
+              Event::KeyPressed { key_input: stroke("f") },
+              Event::KeyReleased { key_input: stroke("f") },
+            ]);
+
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            );
+          }
+
+          it "presses and releases bound key only" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("o")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("o")]);
+          }
+
+          it "grabs following strokes only while prefix is armed" {
+            state.run();
+            assert_eq!(
+              *event_source.grabbed_keys.lock().unwrap(),
+              vec![
+                vec![Grab::new(stroke("x"))],
+                vec![Grab::new(stroke("f"))],
+              ]
+            );
+            assert_eq!(
+              *event_source.ungrabbed_keys.lock().unwrap(),
+              vec![vec![Grab::new(stroke("f"))]]
+            );
+          }
+        }
+
+        context "and the same application is notified while the prefix is armed" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: stroke("x") },
+              Event::KeyReleased { key_input: stroke("x") },
+              Event::ApplicationChanged { next_application: None },
+              Event::KeyPressed { key_input: stroke("f") },
+              Event::KeyReleased { key_input: stroke("f") },
+            ]);
+
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            );
+          }
+
+          it "keeps the sequence" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("o")]);
+          }
+        }
+
+        context "and the sequence is not completed" {
+          before {
+            let event_source = MockEventSource::new(vec![
+              Event::KeyPressed { key_input: stroke("x") },
+              Event::KeyReleased { key_input: stroke("x") },
+              Event::KeyPressed { key_input: stroke("x") },
+            ]);
+
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            );
+          }
+
+          it "re-emits prefix and arms again" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("x")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("x")]);
+            assert_eq!(
+              event_source.grabbed_keys.lock().unwrap().last().cloned(),
+              Some(vec![Grab::new(stroke("f"))])
+            );
+          }
+        }
+      }
+
+      context "when mode keybind exists" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "n": { "enter_mode": "nav" }
+              },
+              "in_app": {},
+              "modes": {
+                "nav": {
+                  "h": { "to": "Left" },
+                  "Escape": { "exit_mode": "nav" }
+                }
+              }
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let executor = MockShellCommandExecutor::new();
+          let key_handler = MockKeyHandler::new();
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+
+          let event_source = MockEventSource::new(vec![
+            Event::KeyPressed { key_input: stroke("n") },
+            Event::KeyPressed { key_input: stroke("h") },
+            Event::KeyReleased { key_input: stroke("h") },
+            Event::KeyPressed { key_input: stroke("Escape") },
+            Event::KeyPressed { key_input: stroke("h") },
+          ]);
+
+          let mut state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source.clone(),
+            key_handler.clone(),
+            executor.clone()
+          );
+        }
+
+        it "maps keys only while mode is active" {
+          state.run();
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("Left"), stroke("h")]);
+          // 押したままのhは終了時に離す
+          assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("Left"), stroke("h")]);
+          assert_eq!(state.modes, vec![]);
+        }
+
+        it "regrabs keys when mode changes" {
+          state.run();
+          assert_eq!(
+            *event_source.grabbed_keys.lock().unwrap(),
+            vec![
+              vec![Grab::new(stroke("n"))],
+              vec![Grab::new(stroke("Escape")), Grab::new(stroke("h"))],
+            ]
+          );
+          assert_eq!(
+            *event_source.ungrabbed_keys.lock().unwrap(),
+            vec![vec![Grab::new(stroke("Escape")), Grab::new(stroke("h"))]]
+          );
+        }
+      }
+
+      context "when remap with optional and ignored modifiers exists" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "h": { "to": "Left", "with": ["Shift"] }
+              },
+              "in_app": {},
+              "ignore_modifiers": ["Lock"]
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let executor = MockShellCommandExecutor::new();
+          let key_handler = MockKeyHandler::new();
+          let input = |key: &str, ms: &[&str]| {
+            KeyInput::of(key.to_string(), ms.iter().map(|m| m.to_string()).collect())
+          };
+
+          let event_source = MockEventSource::new(vec![
+            Event::KeyPressed { key_input: input("h", &["Lock"]) },
+            Event::KeyReleased { key_input: input("h", &["Lock"]) },
+            Event::KeyPressed { key_input: input("h", &["Shift", "Lock"]) },
+            Event::KeyPressed { key_input: input("h", &["Control"]) },
+            Event::KeyReleased { key_input: input("h", &["Control"]) },
+          ]);
+
+          let mut state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source.clone(),
+            key_handler.clone(),
+            executor.clone()
+          );
+        }
+
+        it "grabs the key once with its optional and ignored modifiers" {
+          state.run();
+          assert_eq!(
+            event_source.grabbed_keys.lock().unwrap()[0],
+            vec![
+              Grab::new(input("h", &[]))
+                .with_optional_modifiers(Modifiers::new(vec![Modifier::new("Shift".to_string())]))
+                .with_ignored_modifiers(Modifiers::new(vec![Modifier::new("Lock".to_string())]))
+            ]
+          );
+        }
+
+        it "remaps regardless of ignored modifiers" {
+          state.run();
+          assert_eq!(
+            key_handler.pressed_keys.lock().unwrap()[..2].to_vec(),
+            vec![input("Left", &[]), input("Left", &["Shift"])]
+          );
+          assert_eq!(key_handler.released_keys.lock().unwrap()[0], input("Left", &[]));
+        }
+
+        it "passes through modifiers which are not optional" {
+          state.run();
+          assert_eq!(key_handler.pressed_keys.lock().unwrap()[2], input("h", &["Control"]));
+          assert_eq!(key_handler.released_keys.lock().unwrap().last().cloned(), Some(input("h", &["Control"])));
+        }
+      }
+
+      context "when tap/hold keybind exists" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "Caps_Lock": { "tap": "Escape", "hold": "Control", "hold_timeout": 200 }
+              },
+              "in_app": {}
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let executor = MockShellCommandExecutor::new();
+          let key_handler = MockKeyHandler::new();
+          let clock = MockClock::new();
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+        }
+
+        context "and the key is tapped" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("Caps_Lock") }),
+              (100, Event::KeyReleased { key_input: stroke("Caps_Lock") }),
+            ], clock.clone());
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "sends tap key" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("Escape")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("Escape")]);
+            assert!(!*event_source.keyboard_grabbed.lock().unwrap());
+          }
+        }
+
+        context "and the key is held longer than timeout" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("Caps_Lock") }),
+              (150, Event::KeyPressed { key_input: stroke("a") }),
+              (250, Event::KeyReleased { key_input: stroke("a") }),
+              (300, Event::KeyReleased { key_input: stroke("Caps_Lock") }),
+            ], clock.clone());
+            let with_control = |key: &str| KeyInput::of(key.to_string(), vec!["Control".to_string()]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "adds hold modifier to keys pressed meanwhile" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![with_control("a")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![with_control("a")]);
+            assert!(!*event_source.keyboard_grabbed.lock().unwrap());
+          }
+        }
+
+        context "and another key is pressed and released before timeout" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("Caps_Lock") }),
+              (50, Event::KeyPressed { key_input: stroke("a") }),
+              (80, Event::KeyReleased { key_input: stroke("a") }),
+              (120, Event::KeyReleased { key_input: stroke("Caps_Lock") }),
+            ], clock.clone());
+            let with_control = |key: &str| KeyInput::of(key.to_string(), vec!["Control".to_string()]);
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "treats the key as hold (permissive hold)" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![with_control("a")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![with_control("a")]);
+          }
+        }
+
+        context "and the key is released before another key is released" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("Caps_Lock") }),
+              (50, Event::KeyPressed { key_input: stroke("a") }),
+              (80, Event::KeyReleased { key_input: stroke("Caps_Lock") }),
+              (100, Event::KeyReleased { key_input: stroke("a") }),
+            ], clock.clone());
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "sends tap key and replays rolled key" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("Escape"), stroke("a")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("Escape"), stroke("a")]);
+          }
+        }
+      }
+
+      context "when chord keybind exists" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "j+k": { "to": "Escape" }
+              },
+              "in_app": {},
+              "chord_timeout": 50
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let executor = MockShellCommandExecutor::new();
+          let key_handler = MockKeyHandler::new();
+          let clock = MockClock::new();
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+        }
+
+        context "and the other key is pressed in time" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("k") }),
+              (20, Event::KeyPressed { key_input: stroke("j") }),
+              (80, Event::KeyReleased { key_input: stroke("k") }),
+              (90, Event::KeyReleased { key_input: stroke("j") }),
+            ], clock.clone());
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "sends chord action only" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("Escape")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("Escape")]);
+            assert!(!*event_source.keyboard_grabbed.lock().unwrap());
+          }
+        }
+
+        context "and the other key is not pressed before timeout" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("j") }),
+              (100, Event::KeyPressed { key_input: stroke("k") }),
+              (120, Event::KeyReleased { key_input: stroke("j") }),
+            ], clock.clone());
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "replays buffered key and waits for a new chord" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("j"), stroke("k")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("j"), stroke("k")]);
+          }
+        }
+
+        context "and another key is pressed in time" {
+          before {
+            let event_source = MockEventSource::with_clock(vec![
+              (0, Event::KeyPressed { key_input: stroke("j") }),
+              (10, Event::KeyPressed { key_input: stroke("a") }),
+              (20, Event::KeyReleased { key_input: stroke("a") }),
+              (30, Event::KeyReleased { key_input: stroke("j") }),
+            ], clock.clone());
+            let mut state = State::new(
+              keybind_for_focus,
+              possible_keyinput_finder,
+              event_source.clone(),
+              key_handler.clone(),
+              executor.clone()
+            ).with_clock(clock.clone());
+          }
+
+          it "replays keys in order" {
+            state.run();
+            assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("j"), stroke("a")]);
+            assert_eq!(*key_handler.released_keys.lock().unwrap(), vec![stroke("a"), stroke("j")]);
+            assert!(!*event_source.keyboard_grabbed.lock().unwrap());
+          }
+        }
+      }
+
+      context "when sequence action keybind exists" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"
+            {
+              "remap": {
+                "C-t": { "sequence": ["C-a", { "to": "k", "delay": 100 }] }
+              },
+              "in_app": {}
+            }
+            "#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let event_source = MockEventSource::new(vec![
+            Event::KeyPressed { key_input: KeyInput::of("C-t".to_string(), vec![]) },
+            Event::KeyReleased { key_input: KeyInput::of("C-t".to_string(), vec![]) },
+          ]);
+          let executor = MockShellCommandExecutor::new();
+          let key_handler = MockKeyHandler::new();
+          let clock = MockClock::new();
+          let mut state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source,
+            key_handler.clone(),
+            executor
+          ).with_clock(clock.clone());
+        }
+
+        it "presses and releases each step in order" {
+          state.run();
+          let strokes = vec![KeyInput::of("C-a".to_string(), vec![]), KeyInput::of("k".to_string(), vec![])];
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), strokes);
+          assert_eq!(*key_handler.released_keys.lock().unwrap(), strokes);
+        }
+
+        it "waits before delayed steps" {
+          state.run();
+          assert_eq!(clock.elapsed.get(), std::time::Duration::from_millis(100));
+        }
+      }
+
+      context "when Reload event occured" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let build = |json: &str| {
+            let config: mapper::config::Config = serde_json::from_str(json).unwrap();
+            let parser = mapper::config::Parser::build(&StringIntoDomain);
+            (
+              parser.build_keybind_for_focus(config.clone()).unwrap(),
+              parser.build_possible_keyinput_finder(config).unwrap(),
+            )
+          };
+          let (keybind_for_focus, possible_keyinput_finder) =
+            build(r#"{ "remap": { "a": { "to": "b" } }, "in_app": {} }"#);
+
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+          let event_source = MockEventSource::new(vec![
+            Event::Reload,
+            Event::KeyPressed { key_input: stroke("a") },
+          ]);
+          let key_handler = MockKeyHandler::new();
+          let state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source.clone(),
+            key_handler.clone(),
+            MockShellCommandExecutor::new()
+          );
+        }
+
+        it "swaps config and grabs only added keys" {
+          let reloaded = build(r#"{ "remap": { "a": { "to": "c" }, "x": { "to": "y" } }, "in_app": {} }"#);
+          let mut state = state.with_reloader(MockReloader::new(vec![Ok(reloaded)]));
+          state.run();
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("c")]);
+          assert_eq!(
+            *event_source.grabbed_keys.lock().unwrap(),
+            vec![vec![Grab::new(stroke("a"))], vec![Grab::new(stroke("x"))]]
+          );
+          assert_eq!(event_source.ungrabbed_keys.lock().unwrap().len(), 0);
+        }
+
+        it "keeps current config when new config is invalid" {
+          let mut state = state.with_reloader(MockReloader::new(vec![Err("invalid".to_string())]));
+          state.run();
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("b")]);
+          assert_eq!(event_source.grabbed_keys.lock().unwrap().len(), 1);
+          assert_eq!(event_source.ungrabbed_keys.lock().unwrap().len(), 0);
+        }
+      }
+
+      context "when KeymapChanged event occured" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let config: mapper::config::Config = serde_json::from_str(
+            r#"{ "remap": { "a": { "to": "b" }, "x": { "to": "y" } }, "in_app": {} }"#
+          ).unwrap();
+          let parser = mapper::config::Parser::build(&StringIntoDomain);
+          let keybind_for_focus = parser.build_keybind_for_focus(config.clone()).unwrap();
+          let possible_keyinput_finder = parser.build_possible_keyinput_finder(config).unwrap();
+
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+          let clock = MockClock::new();
+          // 続けて届いた通知は、落ち着いてからまとめて扱う
+          let event_source = MockEventSource::with_clock(
+            vec![(0, Event::KeymapChanged), (10, Event::KeymapChanged)],
+            clock.clone(),
+          );
+          let mut state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source.clone(),
+            MockKeyHandler::new(),
+            MockShellCommandExecutor::new()
+          ).with_clock(clock);
+        }
+
+        it "regrabs every grabbed key once for a burst of notifications" {
+          state.run();
+          let grabs = vec![Grab::new(stroke("a")), Grab::new(stroke("x"))];
+          assert_eq!(*event_source.ungrabbed_keys.lock().unwrap(), vec![grabs.clone()]);
+          assert_eq!(*event_source.grabbed_keys.lock().unwrap(), vec![grabs.clone(), grabs]);
+        }
+      }
+
+      context "when KeymapChanged event occured with a reloader" {
+        before {
+          use mapper::*;
+          use mapper::mock::*;
+          use mapper::config::IsParser;
+
+          let build = |json: &str| {
+            let config: mapper::config::Config = serde_json::from_str(json).unwrap();
+            let parser = mapper::config::Parser::build(&StringIntoDomain);
+            (
+              parser.build_keybind_for_focus(config.clone()).unwrap(),
+              parser.build_possible_keyinput_finder(config).unwrap(),
+            )
+          };
+          // `exclam`をUS配列で変換した設定
+          let (keybind_for_focus, possible_keyinput_finder) =
+            build(r#"{ "remap": { "Shift-1": { "to": "b" } }, "in_app": {} }"#);
+
+          let stroke = |key: &str| KeyInput::of(key.to_string(), vec![]);
+          let event_source = MockEventSource::new(vec![
+            Event::KeymapChanged,
+            Event::KeymapChanged,
+            Event::KeyPressed { key_input: stroke("8") },
+          ]);
+          let key_handler = MockKeyHandler::new();
+          let state = State::new(
+            keybind_for_focus,
+            possible_keyinput_finder,
+            event_source.clone(),
+            key_handler.clone(),
+            MockShellCommandExecutor::new()
+          );
+        }
+
+        it "rebuilds config for the new layout once before the next key and grabs from it" {
+          // AZERTYでは`exclam`はShiftなしで8のkeyから出る
+          let rebuilt = build(r#"{ "remap": { "8": { "to": "b" } }, "in_app": {} }"#);
+          // fileは読み直さない
+          let mut state = state.with_reloader(
+            MockReloader::new(vec![Err("must not reload".to_string())]).with_rebuilt(vec![Ok(rebuilt)])
+          );
+          state.run();
+          let shifted = Grab::new(stroke("Shift-1"));
+          assert_eq!(*event_source.ungrabbed_keys.lock().unwrap(), vec![vec![shifted.clone()]]);
+          assert_eq!(
+            *event_source.grabbed_keys.lock().unwrap(),
+            vec![vec![shifted], vec![Grab::new(stroke("8"))]]
+          );
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("b")]);
+        }
+
+        it "regrabs the current keys when the config cannot be rebuilt" {
+          let mut state = state.with_reloader(MockReloader::new(vec![]).with_rebuilt(vec![Err("invalid".to_string())]));
+          state.run();
+          let shifted = Grab::new(stroke("Shift-1"));
+          assert_eq!(*event_source.ungrabbed_keys.lock().unwrap(), vec![vec![shifted.clone()]]);
+          assert_eq!(*event_source.grabbed_keys.lock().unwrap(), vec![vec![shifted.clone()], vec![shifted]]);
+          assert_eq!(*key_handler.pressed_keys.lock().unwrap(), vec![stroke("8")]);
+        }
+      }
     }
   }
 }